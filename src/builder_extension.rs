@@ -2,12 +2,12 @@ use media_type::{MULTIPART, ALTERNATIVE, RELATED, MIXED};
 use vec1::Vec1;
 
 
-use headers::{HeaderMap, ContentId, ContentDisposition};
-use headers::components::{Disposition, MediaType};
+use headers::{HeaderMap, HeaderTryFrom, ContentId, ContentDisposition, ContentDescription};
+use headers::components::{Disposition, DispositionKind, FileMeta, MediaType, Unstructured};
 use mail::{Resource, Mail, Builder};
 use mail::error::OtherBuilderErrorKind;
 
-use ::resource::EmbeddedWithCId;
+use ::resource::{Embedded, EmbeddedWithCId};
 use ::error::{ExtendedBuilderError, ExtendedBuilderErrorKind};
 
 
@@ -180,12 +180,11 @@ impl BuilderExt for Builder {
 
         builder = builder.body(sub_body)?;
         for embedding in embeddings {
+            let mut headers = create_embedded_headers(DispositionKind::Inline, &embedding)?;
             let (content_id, resource) = embedding.into();
+            headers.insert(ContentId, content_id)?;
             builder = builder.body(
-                Self::create_body_from_resource(resource , headers! {
-                    ContentId: content_id,
-                    ContentDisposition: Disposition::inline()
-                }?)?
+                Self::create_body_from_resource(resource, headers)?
             )?;
         }
         Ok(builder.build()?)
@@ -209,11 +208,10 @@ impl BuilderExt for Builder {
         builder = builder.body(body)?;
 
         for attachment in attachments {
+            let headers = create_embedded_headers(DispositionKind::Attachment, &attachment)?;
             builder = builder.body(Self::create_body_from_resource(
                 attachment.into(),
-                headers! {
-                    ContentDisposition: Disposition::attachment()
-                }?
+                headers
             )?)?;
         }
 
@@ -221,3 +219,105 @@ impl BuilderExt for Builder {
     }
 }
 
+/// creates the headers for the body of an embedding/attachment (except the Content-Id)
+///
+/// This is the Content-Disposition, which file meta contains the file name
+/// and dates of the embedding if it has them, and a Content-Description if
+/// the embedding has a description.
+fn create_embedded_headers(kind: DispositionKind, embedded: &Embedded)
+    -> Result<HeaderMap, ExtendedBuilderError>
+{
+    let mut file_meta = FileMeta::default();
+    file_meta.file_name = embedded.file_name().map(|name| name.to_owned());
+    file_meta.creation_date = embedded.creation_date().cloned();
+    file_meta.modification_date = embedded.modification_date().cloned();
+    file_meta.read_date = embedded.read_date().cloned();
+
+    let mut headers = headers! {
+        ContentDisposition: Disposition::new(kind, file_meta)
+    }?;
+
+    if let Some(description) = embedded.description() {
+        headers.insert(ContentDescription, Unstructured::try_from(description.to_owned())?)?;
+    }
+
+    Ok(headers)
+}
+
+/// Returns all non multipart bodies of the mail, in order.
+#[cfg(test)]
+pub(crate) fn leaf_bodies(mail: &Mail) -> Vec<&Mail> {
+    use mail::MailPart;
+
+    match *mail.body() {
+        MailPart::SingleBody { .. } => vec![mail],
+        MailPart::MultipleBodies { ref bodies, .. } => bodies.iter().flat_map(leaf_bodies).collect()
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use headers::ContentDisposition;
+    use headers::components::Disposition;
+    use mail::{Builder, Mail, Resource};
+
+    use ::resource::{Embedded, EmbeddedWithCId, Disposition as DispositionKind, test_ctx};
+    use super::{BuilderExt, leaf_bodies};
+
+    fn any_body() -> Mail {
+        Builder::create_body_from_resource(Resource::sourceless_from_string("body"), None).unwrap()
+    }
+
+    fn with_attachment(mut embedded: Embedded) -> Mail {
+        let attachment = embedded.assure_content_id_and_copy(&test_ctx());
+        Builder::create_with_attachments(any_body(), vec![attachment], None).unwrap()
+    }
+
+    fn with_embedding(mut embedded: Embedded) -> Mail {
+        let embedding = embedded.assure_content_id_and_copy(&test_ctx());
+        Builder::create_body_with_embeddings(any_body(), vec![embedding].into_iter(), None).unwrap()
+    }
+
+    fn disposition(body: &Mail) -> &Disposition {
+        body.headers().get_single(ContentDisposition)
+            .expect("missing Content-Disposition header")
+            .unwrap()
+    }
+
+    #[test]
+    fn attachment_file_name_is_used_in_content_disposition() {
+        let mut attachment = Embedded::attachment(Resource::sourceless_from_string("abc"));
+        attachment.set_file_name("Übersicht.pdf");
+
+        let mail = with_attachment(attachment);
+
+        let bodies = leaf_bodies(&mail);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(disposition(bodies[1]).file_meta().file_name, Some("Übersicht.pdf".to_owned()));
+    }
+
+    #[test]
+    fn embedding_file_name_is_used_in_content_disposition() {
+        let mut embedding = Embedded::inline(Resource::sourceless_from_string("abc"));
+        embedding.set_file_name("logo.png");
+
+        let mail = with_embedding(embedding);
+
+        let bodies = leaf_bodies(&mail);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(disposition(bodies[1]).file_meta().file_name, Some("logo.png".to_owned()));
+    }
+
+    #[test]
+    fn no_file_name_if_not_set() {
+        let ctx = test_ctx();
+        let attachment = EmbeddedWithCId::new(
+            Resource::sourceless_from_string("abc"), DispositionKind::Attachment, &ctx);
+
+        let mail = Builder::create_with_attachments(any_body(), vec![attachment], None).unwrap();
+
+        assert_eq!(disposition(leaf_bodies(&mail)[1]).file_meta().file_name, None);
+    }
+}
@@ -10,7 +10,7 @@ use headers::components::{
 use ::resource::InspectEmbeddedResources;
 use ::error::{MailSendDataError, MailSendDataErrorKind, WithSource, WithSourceExt};
 
-use super::MailSendData;
+use super::{MailSendData, DuplicateFilenamePolicy};
 
 /// Builder to create `MailSendData`
 pub struct MailSendDataBuilder<'a, TId: ?Sized + 'a, D>
@@ -21,7 +21,8 @@ pub struct MailSendDataBuilder<'a, TId: ?Sized + 'a, D>
     to: Vec<Mailbox>,
     subject: Option<String>,
     template_id: Option<Cow<'a, TId>>,
-    data: Option<D>
+    data: Option<D>,
+    duplicate_filename_policy: DuplicateFilenamePolicy
 }
 
 
@@ -39,6 +40,7 @@ impl<'a, TId: ?Sized + 'a, D> Debug for MailSendDataBuilder<'a, TId, D>
             .field("subject", &self.subject)
             .field("template_id", &self.template_id)
             .field("data", &self.data)
+            .field("duplicate_filename_policy", &self.duplicate_filename_policy)
             .finish()
     }
 }
@@ -63,7 +65,8 @@ impl<'a, TId: ?Sized + 'a, D> MailSendDataBuilder<'a, TId, D>
             to: Vec::new(),
             subject: None,
             template_id: None,
-            data: None
+            data: None,
+            duplicate_filename_policy: DuplicateFilenamePolicy::default()
         }
     }
 
@@ -132,6 +135,14 @@ impl<'a, TId: ?Sized + 'a, D> MailSendDataBuilder<'a, TId, D>
         self
     }
 
+    /// sets the policy used for attachments with the same file name
+    ///
+    /// If not set `DuplicateFilenamePolicy::Uniquify` is used.
+    pub fn duplicate_filename_policy(&mut self, policy: DuplicateFilenamePolicy) -> &mut Self {
+        self.duplicate_filename_policy = policy;
+        self
+    }
+
     //TODO provide custom error
     /// create `MailSendData` from this builder if possible.
     ///
@@ -172,7 +183,8 @@ impl<'a, TId: ?Sized + 'a, D> MailSendDataBuilder<'a, TId, D>
             to: MailboxList(to),
             subject,
             template_id,
-            data
+            data,
+            duplicate_filename_policy: self.duplicate_filename_policy
         })
     }
 
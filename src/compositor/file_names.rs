@@ -0,0 +1,281 @@
+//! Handling of attachments sharing the same file name.
+use std::collections::HashSet;
+
+use headers::components::ContentId;
+
+use ::resource::EmbeddedWithCId;
+use ::error::{DuplicateFileNamesError, CollidingAttachment};
+
+/// Policy deciding what happens if multiple attachments of a mail have the same file name.
+///
+/// Some mail clients only show one of multiple attachments with the same
+/// file name. File names are compared case-insensitive. As the file name
+/// is only encoded (`filename`/`filename*` parameter) when the mail is
+/// encoded, renamed attachments are consistent in both forms.
+///
+/// The file name of an attachment is its `file_name()`, which defaults to
+/// the file name of the source of its resource.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DuplicateFilenamePolicy {
+    /// Rename subsequent duplicates, e.g. `report.pdf`, `report (1).pdf`, `report (2).pdf`.
+    ///
+    /// Only the file names of the attachments placed in the mail are
+    /// changed, the `Embedded` instances they were created from are not
+    /// modified.
+    Uniquify,
+
+    /// Keep duplicate file names.
+    Allow,
+
+    /// Fail composing the mail, listing the colliding attachments.
+    Fail
+}
+
+impl Default for DuplicateFilenamePolicy {
+    fn default() -> Self {
+        DuplicateFilenamePolicy::Uniquify
+    }
+}
+
+/// An attachment renamed by `DuplicateFilenamePolicy::Uniquify`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenamedAttachment {
+    /// the content id of the attachment
+    pub content_id: ContentId,
+
+    /// the file name the attachment had before
+    pub original_file_name: String,
+
+    /// the file name the attachment has now
+    pub file_name: String
+}
+
+impl DuplicateFilenamePolicy {
+
+    /// Applies the policy to the attachments of a mail, returning all renamed attachments.
+    ///
+    /// Composing a mail (`compose` or `compose_from_parts`) applies the policy
+    /// of the `MailSendData` but has no way to report renamed attachments. To get them, apply the policy to
+    /// the attachments yourself and use `Allow` when composing.
+    pub fn apply(self, attachments: &mut [EmbeddedWithCId])
+        -> Result<Vec<RenamedAttachment>, DuplicateFileNamesError>
+    {
+        match self {
+            DuplicateFilenamePolicy::Allow => Ok(Vec::new()),
+            DuplicateFilenamePolicy::Uniquify => Ok(uniquify(attachments)),
+            DuplicateFilenamePolicy::Fail => {
+                let duplicates = find_duplicates(attachments);
+                if duplicates.is_empty() {
+                    Ok(Vec::new())
+                } else {
+                    Err(DuplicateFileNamesError::new(duplicates))
+                }
+            }
+        }
+    }
+}
+
+fn normalize(file_name: &str) -> String {
+    file_name.to_lowercase()
+}
+
+/// returns all attachments which share their file name with another attachment
+fn find_duplicates(attachments: &[EmbeddedWithCId]) -> Vec<CollidingAttachment> {
+    let file_names = attachments.iter()
+        .filter_map(|attachment| attachment.file_name().map(|name| (attachment, name)))
+        .map(|(attachment, file_name)| (attachment, file_name, normalize(file_name)))
+        .collect::<Vec<_>>();
+
+    file_names.iter()
+        .filter(|&&(_, _, ref normalized)| {
+            file_names.iter().filter(|&&(_, _, ref other)| other == normalized).count() > 1
+        })
+        .map(|&(attachment, file_name, _)| CollidingAttachment {
+            file_name: file_name.to_owned(),
+            content_id: attachment.content_id().clone(),
+            source: attachment.resource().source().map(|spec| spec.path.clone())
+        })
+        .collect()
+}
+
+fn uniquify(attachments: &mut [EmbeddedWithCId]) -> Vec<RenamedAttachment> {
+    let file_names = attachments.iter()
+        .map(|attachment| attachment.file_name().map(|name| name.to_owned()))
+        .collect::<Vec<_>>();
+
+    // new names must not clash with a file name used by a later attachment
+    let original = file_names.iter()
+        .filter_map(|file_name| file_name.as_ref())
+        .map(|file_name| normalize(file_name))
+        .collect::<HashSet<_>>();
+
+    let mut used = HashSet::new();
+    let mut renamed = Vec::new();
+    for (attachment, file_name) in attachments.iter_mut().zip(file_names) {
+        let file_name = match file_name {
+            Some(file_name) => file_name,
+            None => continue
+        };
+        if used.insert(normalize(&file_name)) {
+            continue;
+        }
+
+        let new_name = unique_name(&file_name, &original, &used);
+        used.insert(normalize(&new_name));
+        attachment.set_file_name(new_name.clone());
+        renamed.push(RenamedAttachment {
+            content_id: attachment.content_id().clone(),
+            original_file_name: file_name,
+            file_name: new_name
+        });
+    }
+    renamed
+}
+
+fn unique_name(file_name: &str, original: &HashSet<String>, used: &HashSet<String>) -> String {
+    let (stem, extension) = split_extension(file_name);
+    (1usize..)
+        .map(|nr| format!("{} ({}){}", stem, nr, extension))
+        .find(|candidate| {
+            let candidate = normalize(candidate);
+            !original.contains(&candidate) && !used.contains(&candidate)
+        })
+        .unwrap()
+}
+
+/// splits `"report.pdf"` into `("report", ".pdf")`, a leading `.` is not seen as extension
+fn split_extension(file_name: &str) -> (&str, &str) {
+    match file_name.rfind('.') {
+        Some(idx) if idx > 0 => file_name.split_at(idx),
+        _ => (file_name, "")
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use mail::{Resource, ResourceSpec};
+    use headers::components::MediaType;
+
+    use ::resource::{Embedded, EmbeddedWithCId, test_ctx};
+    use super::DuplicateFilenamePolicy;
+
+    fn resource(file_name: &str) -> Resource {
+        Resource::from_spec(ResourceSpec {
+            path: format!("./attachments/{}", file_name).into(),
+            use_name: None,
+            media_type: MediaType::parse("application/octet-stream").unwrap()
+        })
+    }
+
+    fn attachments(file_names: &[&str]) -> Vec<EmbeddedWithCId> {
+        let ctx = test_ctx();
+        file_names.iter()
+            .map(|file_name| EmbeddedWithCId::attachment(resource(file_name), &ctx))
+            .collect()
+    }
+
+    fn file_names(attachments: &[EmbeddedWithCId]) -> Vec<&str> {
+        attachments.iter()
+            .map(|attachment| attachment.file_name().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn uniquify_is_the_default() {
+        assert_eq!(DuplicateFilenamePolicy::default(), DuplicateFilenamePolicy::Uniquify);
+    }
+
+    #[test]
+    fn uniquify_renames_subsequent_duplicates() {
+        let mut attachments = attachments(&[
+            "report.pdf", "Report.PDF", "report (1).pdf", "report.pdf", "notes"
+        ]);
+
+        DuplicateFilenamePolicy::Uniquify.apply(&mut attachments).unwrap();
+
+        assert_eq!(file_names(&attachments), vec![
+            "report.pdf", "Report (2).PDF", "report (1).pdf", "report (3).pdf", "notes"
+        ]);
+    }
+
+    #[test]
+    fn uniquify_returns_the_renamed_attachments() {
+        let mut attachments = attachments(&["a.txt", "b.txt", "A.txt"]);
+
+        let renamed = DuplicateFilenamePolicy::Uniquify.apply(&mut attachments).unwrap();
+
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(&renamed[0].content_id, attachments[2].content_id());
+        assert_eq!(renamed[0].original_file_name, "A.txt");
+        assert_eq!(renamed[0].file_name, "A (1).txt");
+    }
+
+    #[test]
+    fn uniquify_handles_non_ascii_duplicates() {
+        let mut attachments = attachments(&["Übersicht.pdf", "übersicht.pdf"]);
+
+        DuplicateFilenamePolicy::Uniquify.apply(&mut attachments).unwrap();
+
+        assert_eq!(file_names(&attachments), vec!["Übersicht.pdf", "übersicht (1).pdf"]);
+    }
+
+    #[test]
+    fn uniquify_keeps_content_ids_and_the_original_resources() {
+        let ctx = test_ctx();
+        let mut original = Embedded::attachment(resource("a.txt"));
+        let mut attachments = vec![
+            original.assure_content_id_and_copy(&ctx),
+            original.assure_content_id_and_copy(&ctx)
+        ];
+
+        DuplicateFilenamePolicy::Uniquify.apply(&mut attachments).unwrap();
+
+        assert_eq!(attachments[0].content_id(), attachments[1].content_id());
+        assert_eq!(file_names(&attachments), vec!["a.txt", "a (1).txt"]);
+        assert_eq!(original.file_name(), Some("a.txt"));
+    }
+
+    #[test]
+    fn uniquify_uses_the_file_name_of_the_attachment_if_set() {
+        let mut attachments = attachments(&["a.txt", "b.txt", "c.txt"]);
+        attachments[1].set_file_name("A.txt");
+        attachments[2].set_file_name("a (1).txt");
+
+        DuplicateFilenamePolicy::Uniquify.apply(&mut attachments).unwrap();
+
+        assert_eq!(file_names(&attachments), vec!["a.txt", "A (2).txt", "a (1).txt"]);
+    }
+
+    #[test]
+    fn allow_keeps_duplicates() {
+        let mut attachments = attachments(&["report.pdf", "report.pdf"]);
+
+        let renamed = DuplicateFilenamePolicy::Allow.apply(&mut attachments).unwrap();
+
+        assert!(renamed.is_empty());
+        assert_eq!(file_names(&attachments), vec!["report.pdf", "report.pdf"]);
+    }
+
+    #[test]
+    fn fail_lists_the_colliding_attachments() {
+        let mut attachments = attachments(&["Übersicht.pdf", "notes.txt", "ÜBERSICHT.pdf"]);
+
+        let err = DuplicateFilenamePolicy::Fail.apply(&mut attachments).unwrap_err();
+
+        let colliding = err.attachments();
+        assert_eq!(colliding.len(), 2);
+        assert_eq!(colliding[0].file_name, "Übersicht.pdf");
+        assert_eq!(&colliding[0].content_id, attachments[0].content_id());
+        assert_eq!(colliding[0].source, Some("./attachments/Übersicht.pdf".into()));
+        assert_eq!(colliding[1].file_name, "ÜBERSICHT.pdf");
+        assert_eq!(&colliding[1].content_id, attachments[2].content_id());
+        assert_eq!(file_names(&attachments), vec!["Übersicht.pdf", "notes.txt", "ÜBERSICHT.pdf"]);
+    }
+
+    #[test]
+    fn fail_accepts_unique_file_names() {
+        let mut attachments = attachments(&["a.txt", "b.txt"]);
+        assert!(DuplicateFilenamePolicy::Fail.apply(&mut attachments).is_ok());
+    }
+}
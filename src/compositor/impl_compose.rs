@@ -10,8 +10,9 @@ use headers::components::Unstructured;
 use mail::{Mail, Builder, Context};
 
 use ::resource::{
-    Embedded, EmbeddedWithCId,
-    InspectEmbeddedResources, Disposition
+    EmbeddedWithCId, InspectEmbeddedResources,
+    Disposition, assure_content_ids_and_copy,
+    try_for_each_resource, dedup_by_content_id
 };
 use ::builder_extension::{
     BodyPart, BuilderExt
@@ -19,9 +20,9 @@ use ::builder_extension::{
 use ::template_engine::{
     TemplateEngine, MailParts
 };
-use ::error::CompositionError;
+use ::error::{CompositionError, ExtendedBuilderError, ExtendedBuilderErrorKind};
 
-use super::MailSendData;
+use super::{MailSendData, DuplicateFilenamePolicy};
 
 pub(crate) fn compose_mail<'a, C, E, D>(
     ctx: &C,
@@ -33,6 +34,121 @@ pub(crate) fn compose_mail<'a, C, E, D>(
     (Ctx { ctx, engine, _p: PhantomData }).compose_mail(send_data)
 }
 
+/// Composes a mail from already existing `MailParts` instead of using a template engine.
+///
+/// The template id of the send data is ignored, the embeddings and attachments
+/// contained in the data are still added to the mail. As the bodies were
+/// created before, inline embeddings in the data have to have a content id
+/// already (e.g. through `assure_content_ids`), attachments are given one
+/// if needed.
+pub(crate) fn compose_mail_from_parts<'a, C, TId: ?Sized + 'a, D>(
+    ctx: &C,
+    parts: MailParts,
+    send_data: MailSendData<'a, TId, D>
+) -> Result<Mail, ExtendedBuilderError>
+    where C: Context, TId: ToOwned, D: InspectEmbeddedResources
+{
+    let (core_headers, mut data, _template_id, policy) = process_mail_send_data(send_data)?;
+
+    // a content id generated now could not be referenced by the bodies
+    try_for_each_resource(&data, |embedded| {
+        match (embedded.disposition(), embedded.content_id()) {
+            (Disposition::Inline, None) =>
+                Err(ExtendedBuilderErrorKind::InlineEmbeddingWithoutContentId),
+            _ => Ok(())
+        }
+    })?;
+
+    let (embeddings, attachments) = collect_data_resources(ctx, &mut data);
+
+    let MailParts {
+        alternative_bodies,
+        shared_embeddings: mut parts_embeddings,
+        attachments: mut parts_attachments
+    } = parts;
+
+    parts_attachments.extend(attachments);
+    parts_embeddings.extend(embeddings);
+    dedup_by_content_id(&mut parts_embeddings);
+    dedup_by_content_id(&mut parts_attachments);
+
+    // there is no way to report renamed attachments from here
+    policy.apply(&mut parts_attachments)?;
+
+    build_mail(alternative_bodies, parts_embeddings.into_iter(),
+                parts_attachments, core_headers)
+}
+
+fn process_mail_send_data<'n, TId: ?Sized + 'n, D>(
+    send_data: MailSendData<'n, TId, D>
+) -> Result<(
+    HeaderMap,
+    D,
+    Cow<'n, TId>,
+    DuplicateFilenamePolicy
+), ExtendedBuilderError>
+    where TId: ToOwned, D: InspectEmbeddedResources
+{
+    let (sender, from_mailboxes, to_mailboxes, subject, template_id, data, policy)
+        = send_data.destruct();
+
+    // The subject header field
+    let subject = Unstructured::try_from( subject )?;
+
+    // creating the header map
+    let mut core_headers: HeaderMap = headers! {
+        //NOTE: if we support multiple mailboxes in _From we have to
+        // ensure Sender is used _iff_ there is more than one from
+        _From: from_mailboxes,
+        _To: to_mailboxes,
+        Subject: subject
+    }?;
+
+    // add sender header if needed
+    if let Some(sender) = sender {
+        core_headers.insert(Sender, sender)?;
+    }
+
+    Ok((core_headers, data, template_id, policy))
+}
+
+/// assures all `Embedded` instances in data have a content id and returns copies of them
+///
+/// The copies are split into embeddings and attachments based on their disposition.
+/// They can contain multiple copies with the same content id (e.g. from cloned
+/// `Embedded` instances), these are removed once they are merged with the
+/// embeddings and attachments from the template engine.
+fn collect_data_resources<C, D>(ctx: &C, data: &mut D)
+    -> (Vec<EmbeddedWithCId>, Vec<EmbeddedWithCId>)
+    where C: Context, D: InspectEmbeddedResources
+{
+    assure_content_ids_and_copy(data, ctx)
+        .into_iter()
+        .partition(|embedded_wcid| embedded_wcid.disposition() == Disposition::Inline)
+}
+
+/// uses the results of preprocessing data and templates, as well as a list of
+/// mail headers like `_From`,`To`, etc. to create a new mail
+fn build_mail<EMB>(
+    bodies: Vec1<BodyPart>,
+    embeddings: EMB,
+    attachments: Vec<EmbeddedWithCId>,
+    core_headers: HeaderMap
+) -> Result<Mail, ExtendedBuilderError>
+    where EMB: Iterator<Item=EmbeddedWithCId> + ExactSizeIterator
+{
+    let mail = match attachments.len() {
+        0 => Builder::create_alternate_bodies_with_embeddings(
+            bodies, embeddings, Some(core_headers))?,
+        _n => Builder::create_with_attachments(
+            Builder::create_alternate_bodies_with_embeddings(bodies, embeddings, None)?,
+            attachments,
+            Some(core_headers)
+        )?
+    };
+    Ok(mail)
+}
+
 struct Ctx<'a, 'b, C: 'a, E: 'b, D>
     where C: Context, E: TemplateEngine<C, D>, D: InspectEmbeddedResources
 {
@@ -61,49 +177,18 @@ impl<'a, 'b, C, E, D> Ctx<'a, 'b, C, E, D>
         -> Result<Mail, CompositionError<E::Error>>
     {
         //compose display name => create Address with display name;
-        let (core_headers, data, template_id) = self.process_mail_send_data(send_data)?;
+        let (core_headers, data, template_id, policy) = process_mail_send_data(send_data)?;
 
-        let MailParts { alternative_bodies, shared_embeddings, attachments }
+        let MailParts { alternative_bodies, shared_embeddings, mut attachments }
             = self.use_template_engine(&*template_id, data)?;
 
-        let mail = self.build_mail(alternative_bodies, shared_embeddings.into_iter(),
-                                    attachments, core_headers)?;
+        // there is no way to report renamed attachments from here
+        policy.apply(&mut attachments)?;
 
-        Ok(mail)
-    }
+        let mail = build_mail(alternative_bodies, shared_embeddings.into_iter(),
+                                attachments, core_headers)?;
 
-    fn process_mail_send_data<'n>(
-        self,
-        send_data:
-            MailSendData<'n, E::TemplateId, D>
-    ) -> Result<(
-        HeaderMap,
-        D,
-        Cow<'n, E::TemplateId>
-    ), CompositionError<E::Error>>
-        where D: InspectEmbeddedResources
-    {
-        let (sender, from_mailboxes, to_mailboxes, subject, template_id, data)
-            = send_data.destruct();
-
-        // The subject header field
-        let subject = Unstructured::try_from( subject )?;
-
-        // creating the header map
-        let mut core_headers: HeaderMap = headers! {
-            //NOTE: if we support multiple mailboxes in _From we have to
-            // ensure Sender is used _iff_ there is more than one from
-            _From: from_mailboxes,
-            _To: to_mailboxes,
-            Subject: subject
-        }?;
-
-        // add sender header if needed
-        if let Some(sender) = sender {
-            core_headers.insert(Sender, sender)?;
-        }
-
-        Ok((core_headers, data, template_id))
+        Ok(mail)
     }
 
     fn use_template_engine(
@@ -115,16 +200,7 @@ impl<'a, 'b, C, E, D> Ctx<'a, 'b, C, E, D>
         where D: InspectEmbeddedResources
     {
         let mut data = data;
-        let mut embeddings = Vec::new();
-        let mut attachments = Vec::new();
-
-        data.inspect_resources_mut(&mut |embedded: &mut Embedded| {
-            let embedded_wcid = embedded.assure_content_id_and_copy(self.ctx);
-            match embedded_wcid.disposition() {
-                Disposition::Inline => embeddings.push(embedded_wcid),
-                Disposition::Attachment =>  attachments.push(embedded_wcid)
-            }
-        });
+        let (embeddings, attachments) = collect_data_resources(self.ctx, &mut data);
 
         let mut mail_parts = self.engine
             .use_template(template_id, &data, self.ctx)
@@ -132,31 +208,277 @@ impl<'a, 'b, C, E, D> Ctx<'a, 'b, C, E, D>
 
         mail_parts.attachments.extend(attachments);
         mail_parts.shared_embeddings.extend(embeddings);
+        dedup_by_content_id(&mut mail_parts.shared_embeddings);
+        dedup_by_content_id(&mut mail_parts.attachments);
         Ok(mail_parts)
     }
+}
 
 
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use vec1::Vec1;
 
-    /// uses the results of preprocessing data and templates, as well as a list of
-    /// mail headers like `_From`,`To`, etc. to create a new mail
-    fn build_mail<EMB>(
-        self,
-        bodies: Vec1<BodyPart>,
-        embeddings: EMB,
-        attachments: Vec<EmbeddedWithCId>,
-        core_headers: HeaderMap
-    ) -> Result<Mail, CompositionError<E::Error>>
-        where EMB: Iterator<Item=EmbeddedWithCId> + ExactSizeIterator
+    use futures::Future;
+
+    use common::MailType;
+    use common::encoder::EncodingBuffer;
+    use headers::{self, HeaderTryFrom};
+    use headers::components::{ContentId, Email, MediaType};
+    use mail::{Context, Mail, Resource};
+
+    use ::resource::{
+        Embedded, EmbeddedWithCId, Disposition, InspectEmbeddedResources,
+        assure_content_ids, dedup_embedded, test_ctx
+    };
+    use ::builder_extension::{BodyPart, leaf_bodies};
+    use ::template_engine::{TemplateEngine, MailParts};
+    use ::error::{ExtendedBuilderError, ExtendedBuilderErrorKind};
+    use ::compositor::MailSendData;
+
+    fn send_data<D>(data: D) -> MailSendData<'static, str, D>
+        where D: InspectEmbeddedResources
     {
-        let mail = match attachments.len() {
-            0 => Builder::create_alternate_bodies_with_embeddings(
-                bodies, embeddings, Some(core_headers))?,
-            _n => Builder::create_with_attachments(
-                Builder::create_alternate_bodies_with_embeddings(bodies, embeddings, None)?,
-                attachments,
-                Some(core_headers)
-            )?
-        };
-        Ok(mail)
+        let from = Email::try_from("from@hy.test").unwrap().into();
+        let to = Email::try_from("to@hy.test").unwrap().into();
+        MailSendData::simple_new(from, to, "hy there", Cow::Borrowed("template_a"), data)
+    }
+
+    /// content id and file name of every non multipart body of the mail
+    fn describe(mail: &Mail) -> Vec<(Option<ContentId>, Option<String>)> {
+        leaf_bodies(mail).into_iter()
+            .map(|body| {
+                let headers = body.headers();
+                let content_id = headers.get_single(headers::ContentId)
+                    .map(|cid| cid.unwrap().clone());
+                let file_name = headers.get_single(headers::ContentDisposition)
+                    .and_then(|disposition| disposition.unwrap().file_meta().file_name.clone());
+                (content_id, file_name)
+            })
+            .collect()
+    }
+
+    /// encodes the mail, replacing the parts which differ between encodings of the same mail
+    ///
+    /// Multipart boundaries are replaced with `boundary<n>` (in order of appearance),
+    /// the values of the generated `Date` and `Message-ID` headers are removed.
+    fn encode(mail: Mail, ctx: &impl Context) -> String {
+        let encodable = mail.into_encodeable_mail(ctx).wait().unwrap();
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        encodable.encode(&mut encoder).unwrap();
+        let bytes: Vec<u8> = encoder.into();
+        let encoded = String::from_utf8(bytes).unwrap();
+
+        let boundaries = encoded.split("boundary=\"").skip(1)
+            .map(|rest| rest[..rest.find('"').unwrap()].to_owned())
+            .collect::<Vec<_>>();
+
+        let mut normalized = encoded.lines()
+            .map(|line| {
+                if line.starts_with("Date:") {
+                    "Date:".to_owned()
+                } else if line.starts_with("Message-ID:") {
+                    "Message-ID:".to_owned()
+                } else {
+                    line.to_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\r\n");
+
+        for (idx, boundary) in boundaries.iter().enumerate() {
+            normalized = normalized.replace(&**boundary, &format!("boundary{}", idx));
+        }
+        normalized
+    }
+
+    fn body(embeddings: Vec<EmbeddedWithCId>) -> BodyPart {
+        BodyPart {
+            resource: Resource::sourceless_from_string("<img src=\"cid:logo\">"),
+            embeddings
+        }
+    }
+
+    fn parts(bodies: Vec1<BodyPart>) -> MailParts {
+        MailParts {
+            alternative_bodies: bodies,
+            shared_embeddings: Vec::new(),
+            attachments: Vec::new()
+        }
+    }
+
+    fn named(mut embedded: Embedded, file_name: &str) -> Embedded {
+        embedded.set_file_name(file_name);
+        embedded
+    }
+
+    /// template engine returning the same legacy `(bodies, attachments)` tuple on every call
+    struct LegacyEngine {
+        logo_cid: ContentId,
+        terms_cid: ContentId
+    }
+
+    impl LegacyEngine {
+        fn new(ctx: &impl Context) -> Self {
+            LegacyEngine {
+                logo_cid: ctx.generate_content_id(),
+                terms_cid: ctx.generate_content_id()
+            }
+        }
+
+        fn legacy_parts(&self) -> (Vec1<BodyPart>, Vec<EmbeddedWithCId>) {
+            let logo = EmbeddedWithCId::with_cid(
+                Resource::sourceless_from_string("logo"), Disposition::Inline, self.logo_cid.clone());
+            let mut terms = EmbeddedWithCId::with_cid(
+                Resource::sourceless_from_string("terms"), Disposition::Attachment, self.terms_cid.clone());
+            terms.set_file_name("terms.txt");
+
+            (vec1![body(vec![logo])], vec![terms])
+        }
+    }
+
+    impl<C: Context> TemplateEngine<C, Vec<Embedded>> for LegacyEngine {
+        type TemplateId = str;
+        type Error = ExtendedBuilderError;
+
+        fn use_template(&self, _id: &str, _data: &Vec<Embedded>, _ctx: &C)
+            -> Result<MailParts, Self::Error>
+        {
+            Ok(self.legacy_parts().into())
+        }
+    }
+
+    /// template engine returning a body sharing a logo with the other bodies
+    struct SharedLogoEngine {
+        logo_cid: ContentId
+    }
+
+    impl SharedLogoEngine {
+        fn parts(&self) -> MailParts {
+            let logo = EmbeddedWithCId::with_cid(
+                Resource::sourceless_from_string("logo"), Disposition::Inline, self.logo_cid.clone());
+
+            MailParts {
+                alternative_bodies: vec1![body(Vec::new())],
+                shared_embeddings: vec![logo],
+                attachments: Vec::new()
+            }
+        }
+    }
+
+    impl<C: Context> TemplateEngine<C, Vec<Embedded>> for SharedLogoEngine {
+        type TemplateId = str;
+        type Error = ExtendedBuilderError;
+
+        fn use_template(&self, _id: &str, _data: &Vec<Embedded>, _ctx: &C)
+            -> Result<MailParts, Self::Error>
+        {
+            Ok(self.parts())
+        }
+    }
+
+    #[test]
+    fn embeddings_with_the_same_content_id_are_only_added_once() {
+        let ctx = test_ctx();
+        let engine = SharedLogoEngine { logo_cid: ctx.generate_content_id() };
+        // the engine got the logo from the data, which also contains a clone of it
+        let logo = Embedded::with_content_id(
+            Resource::sourceless_from_string("logo"), Disposition::Inline, engine.logo_cid.clone());
+        let data = || vec![logo.clone(), logo.clone()];
+
+        let via_engine = send_data(data()).compose(&ctx, &engine).unwrap();
+        let via_parts = send_data(data()).compose_from_parts(&ctx, engine.parts()).unwrap();
+
+        let expected = vec![
+            (None, None),
+            (Some(engine.logo_cid.clone()), None)
+        ];
+        assert_eq!(describe(&via_engine), expected);
+        assert_eq!(describe(&via_parts), expected);
+    }
+
+    #[test]
+    fn deduplicated_embeddings_are_only_added_once() {
+        let ctx = test_ctx();
+        let media_type = MediaType::parse("image/png").unwrap();
+        // e.g. the same image used for every item in a loop
+        let mut data = (0..3)
+            .map(|_| Embedded::from_bytes(&b"png"[..], media_type.clone(), None, Disposition::Inline))
+            .collect::<Vec<_>>();
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 2);
+        let parts = parts(vec1![body(Vec::new())]);
+        let mail = send_data(data.clone()).compose_from_parts(&ctx, parts).unwrap();
+
+        assert_eq!(describe(&mail), vec![
+            (None, None),
+            (data[0].content_id().cloned(), None)
+        ]);
+    }
+
+    #[test]
+    fn compose_from_parts_adds_the_embeddings_of_the_data() {
+        let ctx = test_ctx();
+        let mut data = vec![
+            Embedded::inline(Resource::sourceless_from_string("banner")),
+            named(Embedded::attachment(Resource::sourceless_from_string("invoice")), "invoice.txt")
+        ];
+        // the content ids have to be known when rendering the bodies
+        assure_content_ids(&mut data, &ctx);
+        let banner_cid = data[0].content_id().cloned();
+
+        let mail = send_data(data).compose_from_parts(&ctx, parts(vec1![body(Vec::new())])).unwrap();
+
+        let bodies = describe(&mail);
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0], (None, None));
+        assert_eq!(bodies[1].0, banner_cid);
+        assert_eq!(bodies[2].1, Some("invoice.txt".to_owned()));
+    }
+
+    #[test]
+    fn compose_from_parts_rejects_inline_embeddings_without_content_id() {
+        let ctx = test_ctx();
+        let data = vec![Embedded::inline(Resource::sourceless_from_string("banner"))];
+
+        let err = send_data(data).compose_from_parts(&ctx, parts(vec1![body(Vec::new())])).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ExtendedBuilderErrorKind::InlineEmbeddingWithoutContentId));
+    }
+
+    #[test]
+    fn compose_from_parts_gives_attachments_of_the_data_a_content_id() {
+        let ctx = test_ctx();
+        let mut data = vec![named(Embedded::attachment(Resource::sourceless_from_string("invoice")), "invoice.txt")];
+
+        send_data(&mut data).compose_from_parts(&ctx, parts(vec1![body(Vec::new())])).unwrap();
+
+        assert!(data[0].content_id().is_some());
+    }
+
+    #[test]
+    fn compose_from_legacy_tuple_matches_compose_with_engine() {
+        let ctx = test_ctx();
+        let engine = LegacyEngine::new(&ctx);
+        let data_cid = ctx.generate_content_id();
+        let data = || vec![
+            named(Embedded::with_content_id(
+                Resource::sourceless_from_string("report"), Disposition::Attachment, data_cid.clone()),
+                "report.txt")
+        ];
+
+        let via_engine = send_data(data()).compose(&ctx, &engine).unwrap();
+        let via_parts = send_data(data()).compose_from_parts(&ctx, engine.legacy_parts()).unwrap();
+
+        let expected = vec![
+            (None, None),
+            (Some(engine.logo_cid.clone()), None),
+            (None, Some("terms.txt".to_owned())),
+            (None, Some("report.txt".to_owned()))
+        ];
+        assert_eq!(describe(&via_engine), expected);
+        assert_eq!(describe(&via_parts), expected);
+        assert_eq!(encode(via_parts, &ctx), encode(via_engine, &ctx));
     }
 }
@@ -12,13 +12,15 @@ use headers::error::ComponentCreationError;
 use mail::{Mail, Context};
 
 use ::resource::InspectEmbeddedResources;
-use ::template_engine::TemplateEngine;
-use ::error::CompositionError;
+use ::template_engine::{TemplateEngine, MailParts};
+use ::error::{CompositionError, ExtendedBuilderError};
 
 mod builder;
 mod impl_compose;
+mod file_names;
 
 pub use self::builder::*;
+pub use self::file_names::{DuplicateFilenamePolicy, RenamedAttachment};
 
 
 /// A type containing all per-Mail specific information
@@ -31,6 +33,7 @@ pub use self::builder::*;
 /// - subject (a String)
 /// - template (a template id, or more concrete `Cow<'a, TId>`, often a cow string)
 /// - data (the data for the template)
+/// - the policy for attachments with the same file name (`DuplicateFilenamePolicy::Uniquify` by default)
 ///
 /// To create a `MailSendData` instance use the `MailSendDataBuilder`.
 ///
@@ -84,7 +87,8 @@ pub struct MailSendData<'a, TId: ?Sized + 'a, D>
     to: MailboxList,
     subject: String,
     template_id: Cow<'a, TId>,
-    data: D
+    data: D,
+    duplicate_filename_policy: DuplicateFilenamePolicy
 }
 
 impl<'a, TId: ?Sized + 'a, D> MailSendData<'a, TId, D>
@@ -100,6 +104,34 @@ impl<'a, TId: ?Sized + 'a, D> MailSendData<'a, TId, D>
         impl_compose::compose_mail(ctx, engine, self)
     }
 
+    /// Compose a mail from given `MailParts` instead of using a template engine.
+    ///
+    /// This can be used if the mail parts are produced by something else
+    /// then a `TemplateEngine`, e.g. a legacy `(bodies, attachments)` tuple
+    /// (which can be converted into `MailParts`, but this conversion is
+    /// deprecated, see `MailParts::from_legacy`).
+    ///
+    /// The template id is ignored but the `Embedded` instances contained in
+    /// the data are still added to the mail in the same way as it is done by
+    /// `compose`. As the bodies already exist they can only refer to inline
+    /// embeddings of the data which have a content id, so call
+    /// `assure_content_ids` on the data before creating the bodies.
+    ///
+    /// # Error
+    ///
+    /// Fails with `ExtendedBuilderErrorKind::InlineEmbeddingWithoutContentId`
+    /// if an inline embedding of the data has no content id. Attachments of
+    /// the data are given a content id if needed.
+    pub fn compose_from_parts<C, P>(
+        self,
+        ctx: &C,
+        parts: P
+    ) -> Result<Mail, ExtendedBuilderError>
+        where C: Context, P: Into<MailParts>
+    {
+        impl_compose::compose_mail_from_parts(ctx, parts.into(), self)
+    }
+
     /// create a simple MailSendData with a sing From and a single To Mailbox
     pub fn simple_new<I>(
         from: Mailbox, to: Mailbox,
@@ -113,7 +145,8 @@ impl<'a, TId: ?Sized + 'a, D> MailSendData<'a, TId, D>
             from: MailboxList(vec1![from]),
             to: MailboxList(vec1![to]),
             subject: subject.into(),
-            template_id, data
+            template_id, data,
+            duplicate_filename_policy: DuplicateFilenamePolicy::default()
         }
     }
 
@@ -171,12 +204,24 @@ impl<'a, TId: ?Sized + 'a, D> MailSendData<'a, TId, D>
         &mut self.data
     }
 
+    /// returns the policy used for attachments with the same file name
+    pub fn duplicate_filename_policy(&self) -> DuplicateFilenamePolicy {
+        self.duplicate_filename_policy
+    }
+
+    /// sets the policy used for attachments with the same file name
+    pub fn set_duplicate_filename_policy(&mut self, policy: DuplicateFilenamePolicy) {
+        self.duplicate_filename_policy = policy;
+    }
+
     pub(crate) fn destruct(self) ->
-        (Option<Mailbox>, MailboxList, MailboxList, String, Cow<'a, TId>, D)
+        (Option<Mailbox>, MailboxList, MailboxList, String, Cow<'a, TId>, D, DuplicateFilenamePolicy)
     {
         //use let destruction to make it more refactoring resistend
-        let MailSendData { sender, from, to, subject, template_id, data } = self;
-        (sender, from, to, subject, template_id, data)
+        let MailSendData {
+            sender, from, to, subject, template_id, data, duplicate_filename_policy
+        } = self;
+        (sender, from, to, subject, template_id, data, duplicate_filename_policy)
     }
 
     pub fn auto_gen_display_names<NC>(&mut self, name_composer: NC) -> Result<(), ComponentCreationError>
@@ -225,6 +270,7 @@ impl<'a, TId: ?Sized + 'a, D> Debug for MailSendData<'a, TId, D>
             .field("subject", &self.subject)
             .field("template_id", &self.template_id)
             .field("data", &self.data)
+            .field("duplicate_filename_policy", &self.duplicate_filename_policy)
             .finish()
     }
 }
@@ -1,6 +1,8 @@
 //! Module contains all custom errors introduced by this crate.
 use std::fmt::{self, Display, Debug};
 use std::mem::drop;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use failure::{Fail, Context, Backtrace};
 
@@ -8,6 +10,7 @@ use headers::error::{
     HeaderTypeError,
     ComponentCreationError
 };
+use headers::components::ContentId;
 use mail::error::{BuilderError, OtherBuilderErrorKind};
 
 
@@ -47,8 +50,10 @@ impl<FT> WithSourceExt for FT
 /// Combines a error with a source into a type potentially implementing Fail
 ///
 /// Fail is implemented if the Source if `Send`, `Sync` and `'static`.
-/// The Display impl always forwards to the contained error as the
-/// source might not implement `Display`
+/// The source is only attached to give it back to the caller, so this
+/// type is transparent: it's displayed as the contained error and the
+/// cause of the contained error is its cause. The source is not included
+/// as it might not implement `Display`.
 #[derive(Debug)]
 pub struct WithSource<E: Fail, S: Debug> {
     error: E,
@@ -111,23 +116,82 @@ impl<E, S> Display for WithSource<E, S>
     where E: Fail, S: Debug
 {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(self.error(), fter)
+        Display::fmt(&self.error, fter)
     }
 }
 
 /// Error returned when composing a `Mail` failed.
-#[derive(Debug, Fail)]
+///
+/// The wrapped error is returned as cause, the Display impl only says
+/// which step failed (and the kind of the builder error, if it has one).
+#[derive(Debug)]
 pub enum CompositionError<TE: Fail> {
     /// It failed to use the underlying template engine.
-    #[fail(display = "{}", _0)]
     Template(TE),
 
     /// It didn't fail to get all `MailParts` but wasn't
     /// able to compose them into an mail.
-    #[fail(display = "{}", _0)]
     Builder(ExtendedBuilderError)
 }
 
+impl<TE> Fail for CompositionError<TE>
+    where TE: Fail
+{
+    fn cause(&self) -> Option<&Fail> {
+        match *self {
+            CompositionError::Template(ref err) => Some(err),
+            CompositionError::Builder(ref err) => Some(err)
+        }
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        match *self {
+            CompositionError::Template(ref err) => err.backtrace(),
+            CompositionError::Builder(ref err) => err.backtrace()
+        }
+    }
+}
+
+impl<TE> Display for CompositionError<TE>
+    where TE: Fail
+{
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompositionError::Template(_) =>
+                write!(fter, "template engine failed to create the mail parts"),
+            CompositionError::Builder(ref err) => match err.kind() {
+                Some(kind) => write!(fter, "building the mail from its parts failed ({:?})", kind),
+                None => write!(fter, "building the mail from its parts failed (mail builder error)")
+            }
+        }
+    }
+}
+
+impl<TE> CompositionError<TE>
+    where TE: Fail
+{
+    /// Return the kind of error which occurred.
+    pub fn kind(&self) -> CompositionErrorKind {
+        match *self {
+            CompositionError::Template(_) => CompositionErrorKind::Template,
+            CompositionError::Builder(_) => CompositionErrorKind::Builder
+        }
+    }
+}
+
+/// Kinds of `CompositionError`, e.g. for matching on them in generic code.
+///
+/// New kinds might be added in the future.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CompositionErrorKind {
+    /// It failed to use the underlying template engine.
+    Template,
+
+    /// It failed to compose the `MailParts` into a mail.
+    Builder
+}
+
 impl<FT, TE> From<FT> for CompositionError<TE>
     where TE: Fail, ExtendedBuilderError: From<FT>
 {
@@ -148,23 +212,83 @@ impl<FT, TE> From<FT> for CompositionError<TE>
 ///
 /// (The builder extension is a trait providing additional methods
 ///  to the `MailBuilder`)
+///
+/// New kinds might be added in the future.
 #[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum ExtendedBuilderErrorKind {
     #[fail(display="need embedding to create a body with an embedding")]
     EmbeddingMissing,
+
+    /// The `DuplicateFileNamesError` listing the attachments is the cause,
+    /// see `ExtendedBuilderError::duplicate_file_names`.
+    #[fail(display="multiple attachments with the same file name")]
+    DuplicateFileNames,
+
+    #[fail(display="inline embedding without content id, it can not be referenced by the bodies")]
+    InlineEmbeddingWithoutContentId,
 }
 
 /// Error returned if building the mail failed.
-#[derive(Debug, Fail)]
+///
+/// This error is transparent, it's displayed as the error it contains
+/// and the cause of the contained error is it's cause.
+#[derive(Debug)]
 pub enum ExtendedBuilderError {
     /// A error covered by `BuilderError` occurred.
-    #[fail(display = "{}", _0)]
     Normal(BuilderError),
 
     /// An error not covered by `BuilderError` occurred.
-    #[fail(display = "{}", _0)]
-    Extended(Context<ExtendedBuilderErrorKind>)
+    Extended(Context<ExtendedBuilderErrorKind>),
+
+}
+
+impl ExtendedBuilderError {
+
+    fn inner(&self) -> &Fail {
+        match *self {
+            ExtendedBuilderError::Normal(ref err) => err,
+            ExtendedBuilderError::Extended(ref err) => err
+        }
+    }
+
+    /// Return the kind of error which occurred.
+    ///
+    /// If the error is a `BuilderError` (the `Normal` variant) `None`
+    /// is returned.
+    pub fn kind(&self) -> Option<ExtendedBuilderErrorKind> {
+        match *self {
+            ExtendedBuilderError::Normal(_) => None,
+            ExtendedBuilderError::Extended(ref context) => Some(*context.get_context())
+        }
+    }
+
+    /// Return the attachments with duplicate file names, if this error was caused by them.
+    ///
+    /// See `DuplicateFilenamePolicy::Fail`.
+    pub fn duplicate_file_names(&self) -> Option<&DuplicateFileNamesError> {
+        match *self {
+            ExtendedBuilderError::Extended(ref context) => context.cause()
+                .and_then(|cause| cause.downcast_ref::<DuplicateFileNamesError>()),
+            _ => None
+        }
+    }
+}
+
+impl Fail for ExtendedBuilderError {
+    fn cause(&self) -> Option<&Fail> {
+        self.inner().cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner().backtrace()
+    }
+}
 
+impl Display for ExtendedBuilderError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(self.inner(), fter)
+    }
 }
 
 impl From<BuilderError> for ExtendedBuilderError {
@@ -183,6 +307,12 @@ impl From<Context<ExtendedBuilderErrorKind>> for ExtendedBuilderError {
         ExtendedBuilderError::Extended(err)
     }
 }
+impl From<DuplicateFileNamesError> for ExtendedBuilderError {
+    fn from(err: DuplicateFileNamesError) -> Self {
+        ExtendedBuilderError::Extended(err.context(ExtendedBuilderErrorKind::DuplicateFileNames))
+    }
+}
+
 impl From<OtherBuilderErrorKind> for ExtendedBuilderError {
     fn from(err: OtherBuilderErrorKind) -> Self {
         ExtendedBuilderError::Normal(err.into())
@@ -241,6 +371,14 @@ pub struct MailSendDataError {
     inner: Context<MailSendDataErrorKind>,
 }
 
+impl MailSendDataError {
+
+    /// Return the kind of error which occurred.
+    pub fn kind(&self) -> MailSendDataErrorKind {
+        *self.inner.get_context()
+    }
+}
+
 impl Fail for MailSendDataError {
     fn cause(&self) -> Option<&Fail> {
         self.inner.cause()
@@ -267,4 +405,293 @@ impl From<Context<MailSendDataErrorKind>> for MailSendDataError {
     fn from(inner: Context<MailSendDataErrorKind>) -> Self {
         MailSendDataError { inner }
     }
-}
\ No newline at end of file
+}
+
+/// An attachment sharing its file name with another attachment of the same mail.
+#[derive(Debug, Clone)]
+pub struct CollidingAttachment {
+    /// the file name of the attachment
+    pub file_name: String,
+
+    /// the content id of the attachment
+    pub content_id: ContentId,
+
+    /// the path of the source of the attachment's resource, if it has a source
+    pub source: Option<PathBuf>
+}
+
+impl Display for CollidingAttachment {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{:?} (content id {}", self.file_name, self.content_id.as_str())?;
+        if let Some(ref source) = self.source {
+            write!(fter, ", source {}", source.display())?;
+        }
+        write!(fter, ")")
+    }
+}
+
+/// Error returned if multiple attachments of a mail have the same file name.
+///
+/// File names are compared case-insensitive.
+#[derive(Debug)]
+pub struct DuplicateFileNamesError {
+    attachments: Vec<CollidingAttachment>
+}
+
+impl DuplicateFileNamesError {
+
+    /// Create a new instance from all colliding attachments.
+    pub(crate) fn new(attachments: Vec<CollidingAttachment>) -> Self {
+        DuplicateFileNamesError { attachments }
+    }
+
+    /// Return all attachments sharing their file name with another attachment, in the order of the attachments.
+    pub fn attachments(&self) -> &[CollidingAttachment] {
+        &self.attachments
+    }
+}
+
+impl Fail for DuplicateFileNamesError {}
+
+impl Display for DuplicateFileNamesError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "multiple attachments with the same file name:")?;
+        for (idx, attachment) in self.attachments.iter().enumerate() {
+            let separator = if idx == 0 { " " } else { ", " };
+            write!(fter, "{}{}", separator, attachment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned if the media type of a file can not be derived from its extension.
+#[derive(Debug)]
+pub struct UnknownFileTypeError {
+    path: PathBuf
+}
+
+impl UnknownFileTypeError {
+
+    /// Create a new instance for the given file path.
+    pub fn new<P>(path: P) -> Self
+        where P: Into<PathBuf>
+    {
+        UnknownFileTypeError { path: path.into() }
+    }
+
+    /// Return the path of the file for which no media type was found.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Fail for UnknownFileTypeError {}
+
+impl Display for UnknownFileTypeError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "can not derive media type of file: {}", self.path.display())
+    }
+}
+
+/// Error kinds associated with decoding a `data:` URI
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+pub enum DataUriErrorKind {
+    /// The URI does not start with `data:`.
+    #[fail(display = "not a data uri")]
+    NotADataUri,
+
+    /// There is no `,` separating the media type from the data.
+    #[fail(display = "data uri is missing the ',' before the data")]
+    MissingData,
+
+    /// The media type of the URI could not be parsed.
+    #[fail(display = "data uri has an invalid media type")]
+    InvalidMediaType,
+
+    /// The data is marked as `base64` but is not valid base64.
+    #[fail(display = "data uri has invalid base64 data")]
+    InvalidBase64,
+
+    /// The data contains a invalid percent encoded sequence.
+    #[fail(display = "data uri has invalid percent encoded data")]
+    InvalidPercentEncoding,
+
+    /// The data is larger than the limit (in bytes) allowed.
+    #[fail(display = "data uri data exceeds the size limit of {} bytes", limit)]
+    TooLarge { limit: usize },
+
+    /// The end of the URI in the html could not be found.
+    #[fail(display = "data uri is not terminated")]
+    Unterminated
+}
+
+/// Error returned if decoding a `data:` URI failed.
+#[derive(Debug)]
+pub struct DataUriError {
+    inner: Context<DataUriErrorKind>,
+}
+
+impl DataUriError {
+
+    /// Return the kind of error which occurred.
+    pub fn kind(&self) -> DataUriErrorKind {
+        *self.inner.get_context()
+    }
+}
+
+impl Fail for DataUriError {
+    fn cause(&self) -> Option<&Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for DataUriError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.inner, fter)
+    }
+}
+
+impl From<DataUriErrorKind> for DataUriError {
+    fn from(kind: DataUriErrorKind) -> Self {
+        DataUriError::from(Context::new(kind))
+    }
+}
+
+impl From<Context<DataUriErrorKind>> for DataUriError {
+    fn from(inner: Context<DataUriErrorKind>) -> Self {
+        DataUriError { inner }
+    }
+}
+
+/// Error returned if one or more `data:` URIs in a html string could not be extracted.
+///
+/// Each failure is returned together with the byte range of the URI in the html.
+#[derive(Debug)]
+pub struct ExtractDataUrisError {
+    failures: Vec<(Range<usize>, DataUriError)>
+}
+
+impl ExtractDataUrisError {
+
+    /// Create a new instance from a non-empty list of failures.
+    pub(crate) fn new(failures: Vec<(Range<usize>, DataUriError)>) -> Self {
+        debug_assert!(!failures.is_empty());
+        ExtractDataUrisError { failures }
+    }
+
+    /// Return the failures which occurred, in the order they appear in the html.
+    pub fn failures(&self) -> &[(Range<usize>, DataUriError)] {
+        &self.failures
+    }
+}
+
+impl Fail for ExtractDataUrisError {
+    fn cause(&self) -> Option<&Fail> {
+        self.failures.first().map(|&(_, ref err)| err as &Fail)
+    }
+}
+
+impl Display for ExtractDataUrisError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "failed to extract {} data uri(s)", self.failures.len())?;
+        if let Some(&(ref range, ref err)) = self.failures.first() {
+            write!(fter, ", first at {}..{}: {}", range.start, range.end, err)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use failure::Fail;
+    use mail::Context;
+    use ::resource::test_ctx;
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn error_types_are_send_and_sync() {
+        assert_send_sync::<ExtendedBuilderError>();
+        assert_send_sync::<MailSendDataError>();
+        assert_send_sync::<CompositionError<MailSendDataError>>();
+        assert_send_sync::<WithSource<MailSendDataError, String>>();
+        assert_send_sync::<DuplicateFileNamesError>();
+        assert_send_sync::<UnknownFileTypeError>();
+        assert_send_sync::<DataUriError>();
+        assert_send_sync::<ExtractDataUrisError>();
+    }
+
+    #[test]
+    fn composition_error_kind_distinguishes_template_and_builder_errors() {
+        let template_err: CompositionError<MailSendDataError> =
+            CompositionError::Template(MailSendDataErrorKind::MissingTo.into());
+        let builder_err: CompositionError<MailSendDataError> =
+            ExtendedBuilderErrorKind::EmbeddingMissing.into();
+
+        assert_eq!(template_err.kind(), CompositionErrorKind::Template);
+        assert_eq!(builder_err.kind(), CompositionErrorKind::Builder);
+    }
+
+    #[test]
+    fn extended_builder_error_kind() {
+        let err = ExtendedBuilderError::from(ExtendedBuilderErrorKind::EmbeddingMissing);
+        assert_eq!(err.kind(), Some(ExtendedBuilderErrorKind::EmbeddingMissing));
+
+        let colliding = CollidingAttachment {
+            file_name: "a".to_owned(),
+            content_id: test_ctx().generate_content_id(),
+            source: None
+        };
+        let err = ExtendedBuilderError::from(DuplicateFileNamesError::new(vec![colliding]));
+        assert_eq!(err.kind(), Some(ExtendedBuilderErrorKind::DuplicateFileNames));
+        assert_eq!(err.duplicate_file_names().unwrap().attachments()[0].file_name, "a");
+        assert_eq!(err.to_string(), "multiple attachments with the same file name");
+        assert_eq!(err.cause().unwrap().to_string(),
+            err.duplicate_file_names().unwrap().to_string());
+
+        let err = ExtendedBuilderError::from(ExtendedBuilderErrorKind::DuplicateFileNames);
+        assert!(err.duplicate_file_names().is_none());
+
+        let err = ExtendedBuilderError::from(OtherBuilderErrorKind::EmptyMultipartBody);
+        assert_eq!(err.kind(), None);
+    }
+
+    #[test]
+    fn composition_error_chains_the_builder_error() {
+        let err: CompositionError<MailSendDataError> =
+            ExtendedBuilderErrorKind::EmbeddingMissing.into();
+
+        assert_eq!(err.to_string(), "building the mail from its parts failed (EmbeddingMissing)");
+
+        let cause = err.cause().expect("builder error should be the cause");
+        assert_eq!(cause.to_string(), "need embedding to create a body with an embedding");
+        assert!(cause.cause().is_none());
+    }
+
+    #[test]
+    fn composition_error_says_which_step_failed() {
+        let err: CompositionError<MailSendDataError> =
+            CompositionError::Template(MailSendDataErrorKind::MissingTo.into());
+        assert_eq!(err.to_string(), "template engine failed to create the mail parts");
+        assert_eq!(err.cause().unwrap().to_string(), "missing data for To field");
+
+        let err: CompositionError<MailSendDataError> =
+            OtherBuilderErrorKind::EmptyMultipartBody.into();
+        assert_eq!(err.to_string(), "building the mail from its parts failed (mail builder error)");
+    }
+
+    #[test]
+    fn with_source_is_displayed_as_the_error() {
+        let err = MailSendDataError::from(MailSendDataErrorKind::MissingTo)
+            .with_source("the source".to_owned());
+
+        assert_eq!(err.to_string(), "missing data for To field");
+        assert!(err.cause().is_none());
+        assert_eq!(err.source(), "the source");
+    }
+}
@@ -47,7 +47,9 @@
 //!    content id**. E.g. a image with content id `"q09cu3@example.com"`
 //!    will be serialized to the string `"q09cu3@example.com"`. This is
 //!    extremely useful for all template engines which use serialization
-//!    as their way to access template data.
+//!    as their way to access template data. The `cid_url` module provides
+//!    functions and the `CidUrl` wrapper to serialize them as `cid:` url
+//!    instead.
 //!
 //!
 //! # Example
@@ -113,12 +115,16 @@ extern crate mail_headers as headers;
 extern crate failure;
 extern crate mime as media_type;
 extern crate futures;
+extern crate chrono;
+extern crate base64;
 extern crate soft_ascii_string;
 #[macro_use]
 extern crate vec1;
 
 #[cfg(feature="serialize-to-content-id")]
 extern crate serde;
+#[cfg(all(test, feature="serialize-to-content-id"))]
+extern crate serde_json;
 
 #[cfg(feature="askama-engine")]
 #[cfg_attr(test, macro_use)]
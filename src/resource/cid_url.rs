@@ -0,0 +1,185 @@
+//! Serialization of embeddings as `cid:` url (feature `serialize-to-content-id`).
+//!
+//! The `Serialize` implementations of `Embedded` and `EmbeddedWithCId`
+//! serialize them **into their content id** (e.g. `"q09cu3@example.com"`)
+//! so templates have to add the `cid:` prefix themselves (e.g.
+//! `<img src="cid:{{data.logo}}">`). The functions in this module can be
+//! used with `#[serde(serialize_with="..")]` to serialize a field as `cid:`
+//! url instead (e.g. `"cid:q09cu3@example.com"`), which then can be used
+//! directly (e.g. `<img src="{{data.logo}}">`).
+//!
+//! As `serialize_with` only applies to the field itself and not to the
+//! elements of e.g. a `Vec<Embedded>` or `Option<Embedded>` field the
+//! `CidUrl` wrapper can be used in this case (e.g. `Vec<CidUrl<Embedded>>`).
+use std::ops::{Deref, DerefMut};
+
+use serde::{Serialize, Serializer, ser};
+
+use super::{Embedded, EmbeddedWithCId, InspectEmbeddedResources};
+
+/// Serializes an `Embedded` as `cid:` url, failing if it has no content id.
+pub fn serialize<S>(embedded: &Embedded, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    match embedded.content_id() {
+        Some(cid) => serializer.serialize_str(&format!("cid:{}", cid.as_str())),
+        None => Err(ser::Error::custom("can not serialize Embedded without content id"))
+    }
+}
+
+/// Serializes an `EmbeddedWithCId` as `cid:` url.
+pub fn serialize_with_cid<S>(embedded: &EmbeddedWithCId, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    serialize(embedded, serializer)
+}
+
+/// A wrapper around `Embedded` or `EmbeddedWithCId` which serializes as `cid:` url.
+///
+/// Serializing a `CidUrl<Embedded>` fails if the embedding has no content id.
+#[derive(Debug, Clone)]
+pub struct CidUrl<T>(pub T);
+
+impl<T> CidUrl<T> {
+    /// returns the wrapped embedding
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for CidUrl<T> {
+    fn from(embedded: T) -> Self {
+        CidUrl(embedded)
+    }
+}
+
+impl<T> Deref for CidUrl<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for CidUrl<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Serialize for CidUrl<Embedded> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl Serialize for CidUrl<EmbeddedWithCId> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serialize_with_cid(&self.0, serializer)
+    }
+}
+
+impl<T> InspectEmbeddedResources for CidUrl<T>
+    where T: InspectEmbeddedResources
+{
+    fn inspect_resources(&self, visitor: &mut FnMut(&Embedded)) {
+        self.0.inspect_resources(visitor)
+    }
+    fn inspect_resources_mut(&mut self, visitor: &mut FnMut(&mut Embedded)) {
+        self.0.inspect_resources_mut(visitor)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+    use mail::Resource;
+
+    use ::resource::{Embedded, EmbeddedWithCId, InspectEmbeddedResources, test_ctx};
+    use super::CidUrl;
+
+    fn any_embedded_with_cid() -> EmbeddedWithCId {
+        EmbeddedWithCId::inline(Resource::sourceless_from_string("abc"), &test_ctx())
+    }
+
+    fn to_json_string(embedded: &Embedded) -> Result<String, serde_json::Error> {
+        let mut out = Vec::new();
+        super::serialize(embedded, &mut serde_json::Serializer::new(&mut out))?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn default_serialization_is_the_content_id() {
+        let emb = any_embedded_with_cid();
+
+        let json = serde_json::to_string(&emb).unwrap();
+        let value: String = serde_json::from_str(&json).unwrap();
+        assert_eq!(value, emb.content_id().as_str());
+    }
+
+    #[test]
+    fn serializes_as_cid_url() {
+        let emb = any_embedded_with_cid();
+
+        let json = to_json_string(&emb).unwrap();
+        let value: String = serde_json::from_str(&json).unwrap();
+        assert_eq!(value, format!("cid:{}", emb.content_id().as_str()));
+    }
+
+    #[test]
+    fn fails_without_content_id() {
+        let emb = Embedded::inline(Resource::sourceless_from_string("abc"));
+        assert!(to_json_string(&emb).is_err());
+    }
+
+    #[test]
+    fn cid_url_serializes_elements_of_a_vec() {
+        let embeddings = vec![CidUrl(any_embedded_with_cid()), CidUrl(any_embedded_with_cid())];
+
+        let json = serde_json::to_string(&embeddings).unwrap();
+        let values: Vec<String> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(values, vec![
+            format!("cid:{}", embeddings[0].content_id().as_str()),
+            format!("cid:{}", embeddings[1].content_id().as_str())
+        ]);
+    }
+
+    #[test]
+    fn cid_url_serializes_options() {
+        let mut emb = Embedded::inline(Resource::sourceless_from_string("abc"));
+        let cid = emb.assure_content_id(&test_ctx()).clone();
+
+        let json = serde_json::to_string(&Some(CidUrl(emb))).unwrap();
+        let value: Option<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(value, Some(format!("cid:{}", cid.as_str())));
+
+        let json = serde_json::to_string(&None::<CidUrl<Embedded>>).unwrap();
+        assert_eq!(json, "null");
+    }
+
+    #[test]
+    fn cid_url_fails_without_content_id() {
+        let embeddings = vec![CidUrl(Embedded::inline(Resource::sourceless_from_string("abc")))];
+        assert!(serde_json::to_string(&embeddings).is_err());
+    }
+
+    #[test]
+    fn cid_url_exposes_the_wrapped_embedding() {
+        let mut embeddings = vec![CidUrl(Embedded::inline(Resource::sourceless_from_string("abc")))];
+
+        let mut count = 0;
+        embeddings.inspect_resources_mut(&mut |emb| {
+            emb.assure_content_id(&test_ctx());
+            count += 1;
+        });
+
+        assert_eq!(count, 1);
+        assert!(embeddings[0].content_id().is_some());
+    }
+}
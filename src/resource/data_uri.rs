@@ -0,0 +1,338 @@
+//! Decoding of `data:` URIs and extracting them from html.
+use std::borrow::Cow;
+use std::ops::Range;
+
+use base64;
+use mail::Context;
+use headers::components::MediaType;
+
+use ::error::{DataUriError, DataUriErrorKind, ExtractDataUrisError};
+use super::{Embedded, EmbeddedWithCId};
+
+/// The default limit for the size of the decoded data of a `data:` URI (10MiB).
+pub const DEFAULT_DATA_URI_SIZE_LIMIT: usize = 10 * 1024 * 1024;
+
+/// media type used if the `data:` URI does not specify one (see RFC 2397)
+const DEFAULT_MEDIA_TYPE: &str = "text/plain;charset=US-ASCII";
+
+/// decodes a `data:` URI into its media type and data
+///
+/// Fails if the decoded data is larger then `size_limit` bytes.
+pub(crate) fn decode_data_uri(uri: &str, size_limit: usize)
+    -> Result<(MediaType, Vec<u8>), DataUriError>
+{
+    if uri.len() < 5 || !uri.as_bytes()[..5].eq_ignore_ascii_case(b"data:") {
+        return Err(DataUriErrorKind::NotADataUri.into());
+    }
+    let rest = &uri[5..];
+    let comma = rest.find(',').ok_or(DataUriErrorKind::MissingData)?;
+    let (header, data) = (&rest[..comma], &rest[comma+1..]);
+
+    let (media_type, is_base64) = match header.rfind(';') {
+        Some(idx) if header[idx+1..].trim().eq_ignore_ascii_case("base64") => (&header[..idx], true),
+        _ => (header, false)
+    };
+
+    let media_type = media_type.trim();
+    let media_type: Cow<str> =
+        if media_type.is_empty() {
+            Cow::Borrowed(DEFAULT_MEDIA_TYPE)
+        } else if media_type.starts_with(';') {
+            // only parameters (e.g. a charset) are given
+            Cow::Owned(format!("text/plain{}", media_type))
+        } else {
+            Cow::Borrowed(media_type)
+        };
+
+    let media_type = MediaType::parse(&*media_type)
+        .map_err(|_| DataUriErrorKind::InvalidMediaType)?;
+
+    let data =
+        if is_base64 {
+            decode_base64(data, size_limit)?
+        } else {
+            percent_decode(data, size_limit)?
+        };
+
+    Ok((media_type, data))
+}
+
+fn decode_base64(data: &str, size_limit: usize) -> Result<Vec<u8>, DataUriError> {
+    // html editors tend to line wrap long base64 data, some also percent encode it
+    let mut cleaned = if data.contains('%') {
+        percent_decode(data, usize::max_value())?
+    } else {
+        data.as_bytes().to_owned()
+    };
+    cleaned.retain(|byte| !byte.is_ascii_whitespace());
+
+    // reject too large data before decoding it
+    if cleaned.len() > (size_limit / 3 + 1) * 4 {
+        return Err(DataUriErrorKind::TooLarge { limit: size_limit }.into());
+    }
+
+    let decoded = base64::decode(&cleaned)
+        .map_err(|_| DataUriErrorKind::InvalidBase64)?;
+
+    if decoded.len() > size_limit {
+        return Err(DataUriErrorKind::TooLarge { limit: size_limit }.into());
+    }
+    Ok(decoded)
+}
+
+fn percent_decode(data: &str, size_limit: usize) -> Result<Vec<u8>, DataUriError> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let byte =
+            if bytes[idx] == b'%' {
+                if idx + 2 >= bytes.len() {
+                    return Err(DataUriErrorKind::InvalidPercentEncoding.into());
+                }
+                match (hex_value(bytes[idx+1]), hex_value(bytes[idx+2])) {
+                    (Some(high), Some(low)) => {
+                        idx += 3;
+                        (high << 4) | low
+                    },
+                    _ => return Err(DataUriErrorKind::InvalidPercentEncoding.into())
+                }
+            } else {
+                idx += 1;
+                bytes[idx-1]
+            };
+
+        if decoded.len() == size_limit {
+            return Err(DataUriErrorKind::TooLarge { limit: size_limit }.into());
+        }
+        decoded.push(byte);
+    }
+    Ok(decoded)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None
+    }
+}
+
+/// Replaces all `data:` URIs in the html with `cid:` URIs referring to new embeddings.
+///
+/// `data:` URIs are only recognized in quoted attribute values (e.g. `src="data:..."`)
+/// and in css `url(...)` values. All embeddings have `Disposition::Inline` and get a
+/// content id generated through the context.
+///
+/// # Error
+///
+/// If any of the `data:` URIs can not be decoded or exceeds `DEFAULT_DATA_URI_SIZE_LIMIT`
+/// an error is returned containing the byte range and cause of every failed URI. In
+/// this case the html is not modified.
+pub fn extract_data_uris(html: &mut String, ctx: &impl Context)
+    -> Result<Vec<EmbeddedWithCId>, ExtractDataUrisError>
+{
+    extract_data_uris_with_limit(html, ctx, DEFAULT_DATA_URI_SIZE_LIMIT)
+}
+
+/// Like `extract_data_uris` but with a custom size limit (in bytes) for each `data:` URI.
+pub fn extract_data_uris_with_limit(
+    html: &mut String,
+    ctx: &impl Context,
+    size_limit: usize
+) -> Result<Vec<EmbeddedWithCId>, ExtractDataUrisError>
+{
+    let mut embeddings = Vec::new();
+    let mut failures = Vec::new();
+    let mut new_html = String::with_capacity(html.len());
+    let mut copied_until = 0;
+
+    for (range, terminated) in find_data_uris(html) {
+        let result =
+            if terminated {
+                Embedded::from_data_uri_with_limit(&html[range.clone()], size_limit)
+            } else {
+                Err(DataUriErrorKind::Unterminated.into())
+            };
+
+        match result {
+            Ok(mut embedded) => {
+                let embedded = embedded.assure_content_id_and_copy(ctx);
+                new_html.push_str(&html[copied_until..range.start]);
+                new_html.push_str("cid:");
+                new_html.push_str(embedded.content_id().as_str());
+                copied_until = range.end;
+                embeddings.push(embedded);
+            },
+            Err(err) => failures.push((range, err))
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(ExtractDataUrisError::new(failures));
+    }
+
+    new_html.push_str(&html[copied_until..]);
+    *html = new_html;
+    Ok(embeddings)
+}
+
+/// returns the byte ranges of all `data:` URIs in the html and if they are terminated
+fn find_data_uris(html: &str) -> Vec<(Range<usize>, bool)> {
+    let bytes = html.as_bytes();
+    let mut found = Vec::new();
+    let mut offset = 0;
+
+    while let Some(pos) = bytes[offset..].windows(5).position(|w| w.eq_ignore_ascii_case(b"data:")) {
+        let start = offset + pos;
+        offset = start + 5;
+
+        let closing = match start.checked_sub(1).map(|idx| (idx, bytes[idx])) {
+            Some((idx, quote @ b'"')) | Some((idx, quote @ b'\'')) => {
+                let before = bytes[..idx].iter().rev().find(|byte| !byte.is_ascii_whitespace());
+                match before {
+                    Some(&b'=') | Some(&b'(') => quote,
+                    _ => continue
+                }
+            },
+            Some((idx, b'(')) if idx >= 3 && bytes[idx-3..idx].eq_ignore_ascii_case(b"url") => b')',
+            _ => continue
+        };
+
+        match bytes[start..].iter().position(|&byte| byte == closing) {
+            Some(len) => {
+                found.push((start..start+len, true));
+                offset = start + len;
+            },
+            None => {
+                // everything following is part of the unterminated uri
+                found.push((start..bytes.len(), false));
+                break;
+            }
+        }
+    }
+    found
+}
+
+
+#[cfg(test)]
+mod test {
+    use ::resource::test_ctx;
+    use super::*;
+
+    mod decode_data_uri {
+        use super::*;
+
+        #[test]
+        fn decodes_base64_data() {
+            let (media_type, data) =
+                decode_data_uri("data:image/png;base64,iVBORw0=", 100).unwrap();
+
+            assert_eq!(media_type, MediaType::parse("image/png").unwrap());
+            assert_eq!(data, vec![0x89, 0x50, 0x4e, 0x47, 0x0d]);
+        }
+
+        #[test]
+        fn ignores_whitespace_in_base64_data() {
+            let (_, data) = decode_data_uri("data:image/png;base64,iVBO\n Rw0=", 100).unwrap();
+            assert_eq!(data, vec![0x89, 0x50, 0x4e, 0x47, 0x0d]);
+        }
+
+        #[test]
+        fn decodes_percent_encoded_data() {
+            let (media_type, data) =
+                decode_data_uri("data:text/plain;charset=utf-8,a%20b%2C", 100).unwrap();
+
+            assert_eq!(media_type, MediaType::parse("text/plain;charset=utf-8").unwrap());
+            assert_eq!(data, b"a b,".to_vec());
+        }
+
+        #[test]
+        fn uses_default_media_type() {
+            let (media_type, _) = decode_data_uri("data:,abc", 100).unwrap();
+            assert_eq!(media_type, MediaType::parse(DEFAULT_MEDIA_TYPE).unwrap());
+        }
+
+        #[test]
+        fn rejects_invalid_uris() {
+            let kind = |uri| decode_data_uri(uri, 100).unwrap_err().kind();
+
+            assert_eq!(kind("http://hy.test"), DataUriErrorKind::NotADataUri);
+            assert_eq!(kind("data:image/png;base64"), DataUriErrorKind::MissingData);
+            assert_eq!(kind("data:image/png;base64,a$b"), DataUriErrorKind::InvalidBase64);
+            assert_eq!(kind("data:text/plain,a%2"), DataUriErrorKind::InvalidPercentEncoding);
+            assert_eq!(kind("data:text/plain,a%zz"), DataUriErrorKind::InvalidPercentEncoding);
+        }
+
+        #[test]
+        fn rejects_too_large_data() {
+            let err = decode_data_uri("data:image/png;base64,iVBORw0=", 4).unwrap_err();
+            assert_eq!(err.kind(), DataUriErrorKind::TooLarge { limit: 4 });
+
+            let err = decode_data_uri("data:text/plain,abcde", 4).unwrap_err();
+            assert_eq!(err.kind(), DataUriErrorKind::TooLarge { limit: 4 });
+        }
+    }
+
+    mod extract_data_uris {
+        use super::*;
+
+        #[test]
+        fn replaces_data_uris_with_cid_uris() {
+            let ctx = test_ctx();
+            let mut html = concat!(
+                r#"<p>data: is kept</p><img src="data:image/png;base64,iVBORw0=">"#,
+                r#"<div style="background: url(data:image/gif;base64,R0lG)"></div>"#
+            ).to_owned();
+
+            let embeddings = extract_data_uris(&mut html, &ctx).unwrap();
+
+            assert_eq!(embeddings.len(), 2);
+            let expected = format!(
+                concat!(
+                    r#"<p>data: is kept</p><img src="cid:{}">"#,
+                    r#"<div style="background: url(cid:{})"></div>"#
+                ),
+                embeddings[0].content_id().as_str(),
+                embeddings[1].content_id().as_str()
+            );
+            assert_eq!(html, expected);
+        }
+
+        #[test]
+        fn reports_all_failures_with_positions() {
+            let ctx = test_ctx();
+            let original = concat!(
+                r#"<img src="data:image/png;base64,a$b">"#,
+                r#"<img src="data:image/png;base64,iVBORw0=">"#,
+                r#"<img src='data:image/png"#
+            );
+            let mut html = original.to_owned();
+
+            let err = extract_data_uris_with_limit(&mut html, &ctx, 4).unwrap_err();
+
+            let failures: Vec<_> = err.failures().iter()
+                .map(|&(ref range, ref err)| (range.clone(), err.kind()))
+                .collect();
+
+            assert_eq!(failures, vec![
+                (10..35, DataUriErrorKind::InvalidBase64),
+                (47..77, DataUriErrorKind::TooLarge { limit: 4 }),
+                (89..103, DataUriErrorKind::Unterminated)
+            ]);
+            assert_eq!(html, original);
+        }
+
+        #[test]
+        fn leaves_html_without_data_uris_unchanged() {
+            let ctx = test_ctx();
+            let mut html = r#"<img src="cid:xx@hy.test">"#.to_owned();
+
+            let embeddings = extract_data_uris(&mut html, &ctx).unwrap();
+
+            assert!(embeddings.is_empty());
+            assert_eq!(html, r#"<img src="cid:xx@hy.test">"#);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Built-in mapping of file extensions to media types.
+use std::path::Path;
+
+use headers::components::MediaType;
+
+/// (extension, media type) pairs used to derive the media type of a file
+static MEDIA_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("webp", "image/webp"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("ics", "text/calendar; charset=utf-8"),
+];
+
+/// returns the media type for the extension of given path, if it's known
+///
+/// The extension is matched case-insensitive.
+pub(crate) fn media_type_from_extension(path: &Path) -> Option<MediaType> {
+    let extension = path.extension()?.to_str()?;
+    MEDIA_TYPES.iter()
+        .find(|&&(ext, _)| ext.eq_ignore_ascii_case(extension))
+        //UNWRAP_SAFE: all media types in the table are valid
+        .map(|&(_, media_type)| MediaType::parse(media_type).unwrap())
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use super::*;
+
+    #[test]
+    fn all_media_types_in_the_table_are_valid() {
+        for &(_, media_type) in MEDIA_TYPES {
+            assert!(MediaType::parse(media_type).is_ok(), "invalid: {}", media_type);
+        }
+    }
+
+    #[test]
+    fn extensions_are_matched_case_insensitive() {
+        assert!(media_type_from_extension(Path::new("logo.png")).is_some());
+        assert!(media_type_from_extension(Path::new("LOGO.PNG")).is_some());
+    }
+
+    #[test]
+    fn unknown_or_missing_extensions_have_no_media_type() {
+        assert!(media_type_from_extension(Path::new("export.dat")).is_none());
+        assert!(media_type_from_extension(Path::new("logo")).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+//! Fingerprints used to find `Embedded` instances with the same content.
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use headers::components::MediaType;
+use mail::ResourceSpec;
+
+/// Identifies the content of an `Embedded` created from a file or in-memory data.
+///
+/// Data keeps (a shared reference to) the bytes, it's compared by its
+/// length and a (non cryptographic) hash first and by the bytes only if
+/// both match, so hash collisions can not merge different data.
+#[derive(Debug, Clone)]
+pub(crate) enum Fingerprint {
+    File {
+        path: PathBuf,
+        modified: Option<SystemTime>,
+        media_type: MediaType
+    },
+    Data {
+        len: usize,
+        hash: u64,
+        bytes: Arc<Vec<u8>>,
+        media_type: MediaType
+    }
+}
+
+impl PartialEq for Fingerprint {
+    fn eq(&self, other: &Self) -> bool {
+        use self::Fingerprint::*;
+        match (self, other) {
+            (
+                &File { ref path, ref modified, ref media_type },
+                &File { path: ref other_path, modified: ref other_modified, media_type: ref other_media_type }
+            ) => {
+                path == other_path && modified == other_modified && media_type == other_media_type
+            },
+            (
+                &Data { len, hash, ref bytes, ref media_type },
+                &Data { len: other_len, hash: other_hash, bytes: ref other_bytes, media_type: ref other_media_type }
+            ) => {
+                len == other_len && hash == other_hash && media_type == other_media_type
+                    && (Arc::ptr_eq(bytes, other_bytes) || bytes == other_bytes)
+            },
+            _ => false
+        }
+    }
+}
+
+impl Fingerprint {
+
+    /// creates the fingerprint of the source of a resource, the file is not accessed
+    pub(crate) fn of_source(spec: &ResourceSpec) -> Self {
+        Fingerprint::of_file(&spec.path, &spec.media_type)
+    }
+
+    /// creates the fingerprint of a file, the file is not accessed
+    pub(crate) fn of_file(path: &Path, media_type: &MediaType) -> Self {
+        Fingerprint::File {
+            path: path.to_owned(),
+            modified: None,
+            media_type: media_type.clone()
+        }
+    }
+
+    /// creates the fingerprint of in-memory data
+    pub(crate) fn of_data(data: Vec<u8>, media_type: &MediaType) -> Self {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        Fingerprint::Data {
+            len: data.len(),
+            hash: hasher.finish(),
+            bytes: Arc::new(data),
+            media_type: media_type.clone()
+        }
+    }
+
+    /// returns the length of the data, if this is the fingerprint of in-memory data
+    pub(crate) fn data_len(&self) -> Option<usize> {
+        match *self {
+            Fingerprint::Data { len, .. } => Some(len),
+            Fingerprint::File { .. } => None
+        }
+    }
+
+    /// returns a copy in which files are identified by their canonical path and current modification time
+    ///
+    /// Files which can not be accessed keep the path they were created with.
+    pub(crate) fn resolve(&self) -> Self {
+        match *self {
+            Fingerprint::File { ref path, ref media_type, .. } => {
+                Fingerprint::File {
+                    path: fs::canonicalize(path).unwrap_or_else(|_| path.clone()),
+                    modified: fs::metadata(path).and_then(|meta| meta.modified()).ok(),
+                    media_type: media_type.clone()
+                }
+            },
+            ref data => data.clone()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text_plain() -> MediaType {
+        MediaType::parse("text/plain").unwrap()
+    }
+
+    #[test]
+    fn same_data_has_the_same_fingerprint() {
+        assert_eq!(
+            Fingerprint::of_data(b"abc".to_vec(), &text_plain()),
+            Fingerprint::of_data(b"abc".to_vec(), &text_plain())
+        );
+        assert_ne!(
+            Fingerprint::of_data(b"abc".to_vec(), &text_plain()),
+            Fingerprint::of_data(b"abd".to_vec(), &text_plain())
+        );
+    }
+
+    #[test]
+    fn data_with_the_same_hash_is_compared_by_content() {
+        let collision = |bytes: &[u8]| Fingerprint::Data {
+            len: 3,
+            hash: 42,
+            bytes: Arc::new(bytes.to_vec()),
+            media_type: text_plain()
+        };
+
+        assert_eq!(collision(b"abc"), collision(b"abc"));
+        assert_ne!(collision(b"abc"), collision(b"abd"));
+    }
+
+    #[test]
+    fn media_type_is_part_of_the_fingerprint() {
+        assert_ne!(
+            Fingerprint::of_data(b"abc".to_vec(), &text_plain()),
+            Fingerprint::of_data(b"abc".to_vec(), &MediaType::parse("text/html").unwrap())
+        );
+    }
+
+    #[test]
+    fn resolve_uses_the_canonical_path() {
+        let relative = Fingerprint::of_file(Path::new("Cargo.toml"), &text_plain());
+        let with_dot = Fingerprint::of_file(Path::new("./src/../Cargo.toml"), &text_plain());
+
+        assert_ne!(relative, with_dot);
+        assert_eq!(relative.resolve(), with_dot.resolve());
+    }
+
+    #[test]
+    fn resolve_keeps_the_path_of_missing_files() {
+        let missing = Fingerprint::of_file(Path::new("./this/file/does/not.exist"), &text_plain());
+        assert_eq!(missing.resolve(), missing);
+    }
+}
@@ -75,8 +75,8 @@ macro_rules! impl_leaf_simple_sized {
 }
 
 impl_leaf_simple_sized! {
-    u8, i8, u16, i16, u32, i32, u128, i128,
-    f32, f64, bool, char,
+    u8, i8, u16, i16, u32, i32, u64, i64, u128, i128,
+    usize, isize, f32, f64, bool, char,
     String, PathBuf, OsString,
 
     //net
@@ -212,6 +212,46 @@ impl<T> InspectEmbeddedResources for Option<T>
     }
 }
 
+impl InspectEmbeddedResources for () {
+    fn inspect_resources(&self, _visitor: &mut FnMut(&Embedded)) {
+        //nop
+    }
+    fn inspect_resources_mut(&mut self, _visitor: &mut FnMut(&mut Embedded)) {
+        //nop
+    }
+}
+
+macro_rules! impl_tuple {
+    ($([$($name:ident),*]),*) => ($(
+        impl<$($name),*> InspectEmbeddedResources for ($($name,)*)
+            where $($name: InspectEmbeddedResources),*
+        {
+            #[allow(non_snake_case)]
+            fn inspect_resources(&self, visitor: &mut FnMut(&Embedded)) {
+                let &($(ref $name,)*) = self;
+                $($name.inspect_resources(visitor);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn inspect_resources_mut(&mut self, visitor: &mut FnMut(&mut Embedded)) {
+                let &mut ($(ref mut $name,)*) = self;
+                $($name.inspect_resources_mut(visitor);)*
+            }
+        }
+    )*);
+}
+
+impl_tuple! {
+    [A],
+    [A, B],
+    [A, B, C],
+    [A, B, C, D],
+    [A, B, C, D, E],
+    [A, B, C, D, E, F],
+    [A, B, C, D, E, F, G],
+    [A, B, C, D, E, F, G, H]
+}
+
 
 #[cfg(test)]
 mod test {
@@ -275,4 +315,66 @@ mod test {
             assert_eq!(counter, 6)
         }
     }
-}
\ No newline at end of file
+
+    mod containers {
+        use std::collections::{HashMap, BTreeMap, VecDeque};
+        use mail::Resource;
+        use ::resource::{InspectEmbeddedResources, Embedded};
+
+        fn any_embedded() -> Embedded {
+            Embedded::inline(Resource::sourceless_from_string("abc"))
+        }
+
+        fn count(data: &InspectEmbeddedResources) -> usize {
+            let mut counter = 0;
+            data.inspect_resources(&mut |_| counter += 1);
+            counter
+        }
+
+        fn count_mut(data: &mut InspectEmbeddedResources) -> usize {
+            let mut counter = 0;
+            data.inspect_resources_mut(&mut |_| counter += 1);
+            counter
+        }
+
+        #[test]
+        fn leaf_types_contain_no_embeddings() {
+            let mut data = (12u64, -3i64, 4usize, "text".to_owned(), (), 1.5f64);
+            assert_eq!(count(&data), 0);
+            assert_eq!(count_mut(&mut data), 0);
+        }
+
+        #[test]
+        fn nested_containers_are_inspected() {
+            let mut map = HashMap::new();
+            map.insert(1u64, Box::new(any_embedded()));
+            map.insert(2u64, Box::new(any_embedded()));
+
+            let mut tree = BTreeMap::new();
+            tree.insert("a".to_owned(), vec![Some(any_embedded()), None]);
+
+            let mut deque = VecDeque::new();
+            deque.push_back((42u32, any_embedded()));
+
+            let mut data = (
+                vec![Some(any_embedded()), None, Some(any_embedded())],
+                map,
+                tree,
+                deque,
+                Some(Box::new((any_embedded(), "text".to_owned())))
+            );
+
+            assert_eq!(count(&data), 7);
+            assert_eq!(count_mut(&mut data), 7);
+        }
+
+        #[test]
+        fn mutable_references_forward_to_the_referenced_value() {
+            let mut emb = any_embedded();
+            let mut data = (&mut emb, Some(any_embedded()));
+
+            assert_eq!(count(&data), 2);
+            assert_eq!(count_mut(&mut data), 2);
+        }
+    }
+}
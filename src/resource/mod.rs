@@ -1,14 +1,32 @@
 use std::ops::Deref;
+use std::mem;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use chrono::Utc;
 
 use mail::Context;
-use headers::components::ContentId;
-use mail::Resource;
+use headers::components::{ContentId, DateTime, MediaType};
+use mail::{Resource, ResourceSpec};
 #[cfg(feature="serialize-to-content-id")]
 use serde::{Serialize, Serializer, ser};
 
 pub use headers::components::DispositionKind as Disposition;
+pub use self::utils::*;
+pub use self::data_uri::{extract_data_uris, extract_data_uris_with_limit, DEFAULT_DATA_URI_SIZE_LIMIT};
+
+use ::error::{UnknownFileTypeError, DataUriError};
+use self::fingerprint::Fingerprint;
 
 mod impl_inspect;
+mod utils;
+mod file_types;
+mod data_uri;
+mod fingerprint;
+#[cfg(feature="serialize-to-content-id")]
+pub mod cid_url;
 
 /// Represents any leaf body which is not a main body of an mail.
 ///
@@ -41,12 +59,19 @@ mod impl_inspect;
 /// Normally this struct would not be serializeable
 /// (Resource isn't) but for template engines which
 /// use serialization for data access serializing it
-/// to it's content id string is quite use full
+/// to it's content id string is quite use full.
+/// To serialize it as `cid:` url instead see the `cid_url` module and its `CidUrl` wrapper.
 #[derive(Debug, Clone)]
 pub struct Embedded {
     content_id: Option<ContentId>,
     resource: Resource,
     disposition: Disposition,
+    file_name: Option<String>,
+    description: Option<String>,
+    creation_date: Option<DateTime>,
+    modification_date: Option<DateTime>,
+    read_date: Option<DateTime>,
+    fingerprint: Option<Fingerprint>,
 }
 
 impl Embedded {
@@ -62,21 +87,135 @@ impl Embedded {
     }
 
     /// Create a new embedding from a resource using given disposition.
+    ///
+    /// If the resource has a source the file name of the embedding defaults
+    /// to the file name of the source (or of it's `use_name` if given).
+    /// Embeddings of resources with a source are considered by `dedup_embedded`.
     pub fn new(resource: Resource, disposition: Disposition) -> Self {
+        let file_name = source_file_name(&resource);
+        let fingerprint = resource.source().map(Fingerprint::of_source);
         Embedded {
             content_id: None,
             resource,
-            disposition
+            disposition,
+            file_name,
+            description: None,
+            creation_date: None,
+            modification_date: None,
+            read_date: None,
+            fingerprint
         }
     }
 
+    /// Create a inline embedding from a file.
+    ///
+    /// The media type is derived from the file extension and the file name
+    /// is used as file name of the embedding. The file is not accessed until
+    /// the resource is loaded. See `Embedded::from_file` for file names which
+    /// are not valid utf-8.
+    ///
+    /// # Error
+    ///
+    /// If the file has no or an unknown extension.
+    pub fn inline_file<P>(path: P) -> Result<Self, UnknownFileTypeError>
+        where P: AsRef<Path>
+    {
+        Embedded::from_file(path, Disposition::Inline, None)
+    }
+
+    /// Create a attachment embedding from a file.
+    ///
+    /// The media type is derived from the file extension and the file name
+    /// is used as file name of the embedding. The file is not accessed until
+    /// the resource is loaded. See `Embedded::from_file` for file names which
+    /// are not valid utf-8.
+    ///
+    /// # Error
+    ///
+    /// If the file has no or an unknown extension.
+    pub fn attachment_file<P>(path: P) -> Result<Self, UnknownFileTypeError>
+        where P: AsRef<Path>
+    {
+        Embedded::from_file(path, Disposition::Attachment, None)
+    }
+
+    /// Create a new embedding from a file using given disposition.
+    ///
+    /// The media type is derived from the file extension, if the extension
+    /// is unknown `fallback_media_type` is used instead (so you can e.g. pass
+    /// in `application/octet-stream`).
+    ///
+    /// The file name is used as file name of the embedding. If it is not valid
+    /// utf-8 no file name is set (instead of a lossy conversion, which would
+    /// contain replacement characters), callers can set one using `set_file_name`.
+    ///
+    /// # Error
+    ///
+    /// If the file has no or an unknown extension and no fallback media type
+    /// was given.
+    pub fn from_file<P>(
+        path: P,
+        disposition: Disposition,
+        fallback_media_type: Option<MediaType>
+    ) -> Result<Self, UnknownFileTypeError>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let media_type = file_types::media_type_from_extension(path)
+            .or(fallback_media_type)
+            .ok_or_else(|| UnknownFileTypeError::new(path))?;
+
+        let resource = Resource::from_spec(ResourceSpec {
+            path: path.to_owned(),
+            use_name: None,
+            media_type
+        });
+
+        // the file name and fingerprint are derived from the resource's source
+        Ok(Embedded::new(resource, disposition))
+    }
+
+    /// Create a new embedding from in-memory data.
+    ///
+    /// A copy of the data is kept to find embeddings with the same data
+    /// in `dedup_embedded`.
+    pub fn from_bytes<B>(
+        bytes: B,
+        media_type: MediaType,
+        file_name: Option<String>,
+        disposition: Disposition
+    ) -> Self
+        where B: Into<Vec<u8>>
+    {
+        let bytes: Vec<u8> = bytes.into();
+        let fingerprint = Fingerprint::of_data(bytes.clone(), &media_type);
+        let mut embedded = Embedded::new(Resource::sourceless(media_type, bytes), disposition);
+        embedded.file_name = file_name;
+        embedded.fingerprint = Some(fingerprint);
+        embedded
+    }
+
+    /// Create a new inline embedding from a `data:` URI.
+    ///
+    /// The `base64` or percent encoded data is decoded and kept in memory,
+    /// if it is larger then `DEFAULT_DATA_URI_SIZE_LIMIT` an error is returned.
+    pub fn from_data_uri(uri: &str) -> Result<Self, DataUriError> {
+        Embedded::from_data_uri_with_limit(uri, DEFAULT_DATA_URI_SIZE_LIMIT)
+    }
+
+    /// Create a new inline embedding from a `data:` URI using a custom size limit (in bytes).
+    pub fn from_data_uri_with_limit(uri: &str, size_limit: usize) -> Result<Self, DataUriError> {
+        let (media_type, data) = data_uri::decode_data_uri(uri, size_limit)?;
+        Ok(Embedded::from_bytes(data, media_type, None, Disposition::Inline))
+    }
+
     /// Create a new embedding from a `Resource` using given disposition and given content id.
+    ///
+    /// The file name defaults to the file name of the resource's source, see `Embedded::new`.
     pub fn with_content_id(resource: Resource, disposition: Disposition, content_id: ContentId) -> Self {
-        Embedded {
-            content_id: Some(content_id),
-            resource,
-            disposition
-        }
+        let mut embedded = Embedded::new(resource, disposition);
+        embedded.content_id = Some(content_id);
+        embedded
     }
 
     /// Return a reference to the contained resource.
@@ -85,7 +224,11 @@ impl Embedded {
     }
 
     /// Return a mutable reference to the contained resource.
+    ///
+    /// As the resource might be replaced the embedding is no longer
+    /// considered by `dedup_embedded`.
     pub fn resource_mut(&mut self) -> &mut Resource {
+        self.fingerprint = None;
         &mut self.resource
     }
 
@@ -94,11 +237,149 @@ impl Embedded {
         self.content_id.as_ref()
     }
 
+    /// Set the content id of the embedding returning the previous one, if any.
+    ///
+    /// This can be used to use pre-computed content ids instead of generating
+    /// them using a `Context`.
+    pub fn set_content_id(&mut self, content_id: ContentId) -> Option<ContentId> {
+        mem::replace(&mut self.content_id, Some(content_id))
+    }
+
+    /// Remove the content id from the embedding and return it, if any.
+    pub fn take_content_id(&mut self) -> Option<ContentId> {
+        self.content_id.take()
+    }
+
     /// Return a reference to disposition to use for the embedding.
     pub fn disposition(&self) -> Disposition {
         self.disposition
     }
 
+    /// Return a reference to the file name to use for the embedding, if any.
+    ///
+    /// If set it's used as `filename` parameter of the Content-Disposition
+    /// header of the body created from this embedding.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_ref().map(|name| &**name)
+    }
+
+    /// Set the file name to use for the embedding returning the previous one, if any.
+    ///
+    /// The file name can contain non us-ascii characters, it's encoded
+    /// as needed when the mail is encoded.
+    pub fn set_file_name<I>(&mut self, file_name: I) -> Option<String>
+        where I: Into<String>
+    {
+        mem::replace(&mut self.file_name, Some(file_name.into()))
+    }
+
+    /// Remove the file name from the embedding and return it, if any.
+    pub fn take_file_name(&mut self) -> Option<String> {
+        self.file_name.take()
+    }
+
+    /// Return a reference to the description of the embedding, if any.
+    ///
+    /// If set it's used as Content-Description header of the body created
+    /// from this embedding.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_ref().map(|desc| &**desc)
+    }
+
+    /// Set the description of the embedding returning the previous one, if any.
+    ///
+    /// Descriptions containing non us-ascii characters are encoded using
+    /// encoded words when the mail is encoded.
+    pub fn set_description<I>(&mut self, description: I) -> Option<String>
+        where I: Into<String>
+    {
+        mem::replace(&mut self.description, Some(description.into()))
+    }
+
+    /// Remove the description from the embedding and return it, if any.
+    pub fn take_description(&mut self) -> Option<String> {
+        self.description.take()
+    }
+
+    /// Return a reference to the creation date of the embedding, if any.
+    ///
+    /// If set it's used as `creation-date` parameter of the Content-Disposition
+    /// header of the body created from this embedding.
+    pub fn creation_date(&self) -> Option<&DateTime> {
+        self.creation_date.as_ref()
+    }
+
+    /// Set the creation date of the embedding returning the previous one, if any.
+    pub fn set_creation_date(&mut self, date: DateTime) -> Option<DateTime> {
+        mem::replace(&mut self.creation_date, Some(date))
+    }
+
+    /// Remove the creation date from the embedding and return it, if any.
+    pub fn take_creation_date(&mut self) -> Option<DateTime> {
+        self.creation_date.take()
+    }
+
+    /// Return a reference to the modification date of the embedding, if any.
+    ///
+    /// If set it's used as `modification-date` parameter of the Content-Disposition
+    /// header of the body created from this embedding.
+    pub fn modification_date(&self) -> Option<&DateTime> {
+        self.modification_date.as_ref()
+    }
+
+    /// Set the modification date of the embedding returning the previous one, if any.
+    pub fn set_modification_date(&mut self, date: DateTime) -> Option<DateTime> {
+        mem::replace(&mut self.modification_date, Some(date))
+    }
+
+    /// Remove the modification date from the embedding and return it, if any.
+    pub fn take_modification_date(&mut self) -> Option<DateTime> {
+        self.modification_date.take()
+    }
+
+    /// Return a reference to the read date of the embedding, if any.
+    ///
+    /// If set it's used as `read-date` parameter of the Content-Disposition
+    /// header of the body created from this embedding.
+    pub fn read_date(&self) -> Option<&DateTime> {
+        self.read_date.as_ref()
+    }
+
+    /// Set the read date of the embedding returning the previous one, if any.
+    pub fn set_read_date(&mut self, date: DateTime) -> Option<DateTime> {
+        mem::replace(&mut self.read_date, Some(date))
+    }
+
+    /// Remove the read date from the embedding and return it, if any.
+    pub fn take_read_date(&mut self) -> Option<DateTime> {
+        self.read_date.take()
+    }
+
+    /// Set the creation and modification date based on the metadata of given file.
+    ///
+    /// Dates which are not available on the current platform are left
+    /// unchanged. The read date is not set, as the access time of a file
+    /// says nothing about the recipient having read it and often is not
+    /// updated at all (e.g. `noatime` mounts), use `set_read_date` instead.
+    ///
+    /// # Error
+    ///
+    /// If the metadata of the file can not be accessed.
+    pub fn set_dates_from_file_metadata<P>(&mut self, path: P) -> Result<(), io::Error>
+        where P: AsRef<Path>
+    {
+        let meta = fs::metadata(path)?;
+        let to_date = |time: SystemTime| DateTime::new(::chrono::DateTime::<Utc>::from(time));
+
+        if let Ok(time) = meta.created() {
+            self.creation_date = Some(to_date(time));
+        }
+        if let Ok(time) = meta.modified() {
+            self.modification_date = Some(to_date(time));
+        }
+        Ok(())
+    }
+
     /// Generate and set a new content id if this embedding doesn't have a content id.
     pub fn assure_content_id(&mut self, ctx: &impl Context) -> &ContentId {
         if self.content_id.is_none() {
@@ -118,6 +399,21 @@ impl Embedded {
     }
 }
 
+/// returns the file name of the source of the resource, if it has a source
+fn source_file_name(resource: &Resource) -> Option<String> {
+    resource.source().and_then(|spec| {
+        let path = spec.use_name.as_ref().unwrap_or(&spec.path);
+        file_name_of(path)
+    })
+}
+
+/// returns the file name of the path, if it has one which is valid utf-8
+fn file_name_of(path: &Path) -> Option<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_owned())
+}
+
 #[cfg(feature="serialize-to-content-id")]
 impl<'a> Serialize for Embedded {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -215,9 +511,112 @@ impl<'a> Serialize for Embedded {
 ///
 /// # fn main() {}
 /// ```
+///
+/// Combined with `Embedded::assure_content_id` the derive can be used to give
+/// all embeddings in the data a content id before it's passed to a template
+/// engine (which is what `MailSendData::compose` does):
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_template;
+/// # extern crate mail_types as mail;
+/// # extern crate mail_headers as headers;
+/// # extern crate soft_ascii_string;
+/// # use soft_ascii_string::SoftAsciiString;
+/// # use headers::HeaderTryFrom;
+/// # use headers::components::Domain;
+/// # use mail::default_impl::simple_context;
+/// use mail::Resource;
+/// use mail_template::{Embedded, InspectEmbeddedResources};
+///
+/// #[derive(InspectEmbeddedResources)]
+/// struct Newsletter {
+///     title: String,
+///     logo: Embedded,
+///     articles: Vec<Article>
+/// }
+///
+/// #[derive(InspectEmbeddedResources)]
+/// struct Article {
+///     text: String,
+///     image: Option<Embedded>
+/// }
+///
+/// # fn main() {
+/// # let ctx = simple_context::new(
+/// #     Domain::try_from("example.test").unwrap(),
+/// #     SoftAsciiString::from_unchecked("9ddqdq")
+/// # ).unwrap();
+/// # let logo = Resource::sourceless_from_string("should be an image");
+/// # let image = Resource::sourceless_from_string("should be an image");
+/// let mut data = Newsletter {
+///     title: "News".to_owned(),
+///     logo: Embedded::inline(logo),
+///     articles: vec![
+///         Article { text: "with image".to_owned(), image: Some(Embedded::inline(image)) },
+///         Article { text: "without image".to_owned(), image: None }
+///     ]
+/// };
+///
+/// data.inspect_resources_mut(&mut |embedded: &mut Embedded| {
+///     embedded.assure_content_id(&ctx);
+/// });
+///
+/// let mut count = 0;
+/// data.inspect_resources(&mut |embedded: &Embedded| {
+///     assert!(embedded.content_id().is_some());
+///     count += 1;
+/// });
+/// assert_eq!(count, 2);
+/// # }
+/// ```
 pub trait InspectEmbeddedResources {
     fn inspect_resources(&self, visitor: &mut FnMut(&Embedded));
     fn inspect_resources_mut(&mut self, visitor: &mut FnMut(&mut Embedded));
+
+    /// Like `inspect_resources` but the visitor can fail.
+    ///
+    /// Once the visitor returned an error it is not called again and the
+    /// error is returned. As this is implemented on top of `inspect_resources`
+    /// all remaining `Embedded` instances are still traversed (just without
+    /// calling the visitor), so returning an error doesn't make searching
+    /// for a matching `Embedded` instance any cheaper.
+    fn try_inspect_resources<E>(
+        &self,
+        visitor: &mut FnMut(&Embedded) -> Result<(), E>
+    ) -> Result<(), E>
+        where Self: Sized
+    {
+        let mut result = Ok(());
+        self.inspect_resources(&mut |embedded: &Embedded| {
+            if result.is_ok() {
+                result = visitor(embedded);
+            }
+        });
+        result
+    }
+
+    /// Like `inspect_resources_mut` but the visitor can fail.
+    ///
+    /// Once the visitor returned an error it is not called again and the
+    /// error is returned. As this is implemented on top of `inspect_resources_mut`
+    /// all remaining `Embedded` instances are still traversed (just without
+    /// calling the visitor), so returning an error doesn't make searching
+    /// for a matching `Embedded` instance any cheaper.
+    fn try_inspect_resources_mut<E>(
+        &mut self,
+        visitor: &mut FnMut(&mut Embedded) -> Result<(), E>
+    ) -> Result<(), E>
+        where Self: Sized
+    {
+        let mut result = Ok(());
+        self.inspect_resources_mut(&mut |embedded: &mut Embedded| {
+            if result.is_ok() {
+                result = visitor(embedded);
+            }
+        });
+        result
+    }
 }
 
 
@@ -232,7 +631,8 @@ impl InspectEmbeddedResources for Embedded {
 
 impl Into<Resource> for Embedded {
     fn into(self) -> Resource {
-        let Embedded { content_id:_, resource, disposition:_ } = self;
+        let Embedded { content_id:_, resource, disposition:_, file_name:_, description:_,
+            creation_date:_, modification_date:_, read_date:_, fingerprint:_ } = self;
         resource
     }
 }
@@ -247,7 +647,8 @@ impl Into<Resource> for Embedded {
 /// Normally this struct would not be serializeable
 /// (Resource isn't) but for template engines which
 /// use serialization for data access serializing it
-/// to it's content id string is quite use full
+/// to it's content id string is quite use full.
+/// To serialize it as `cid:` url instead see the `cid_url` module and its `CidUrl` wrapper.
 #[derive(Debug, Clone)]
 pub struct EmbeddedWithCId {
     inner: Embedded
@@ -286,6 +687,65 @@ impl EmbeddedWithCId {
         }
     }
 
+    /// create a new inline embedding from a file
+    ///
+    /// See `Embedded::inline_file`, the context is used to generate a fitting content id.
+    pub fn inline_file<P>(path: P, ctx: &impl Context) -> Result<Self, UnknownFileTypeError>
+        where P: AsRef<Path>
+    {
+        EmbeddedWithCId::from_file(path, Disposition::Inline, None, ctx)
+    }
+
+    /// create a new attachment embedding from a file
+    ///
+    /// See `Embedded::attachment_file`, the context is used to generate a fitting content id.
+    pub fn attachment_file<P>(path: P, ctx: &impl Context) -> Result<Self, UnknownFileTypeError>
+        where P: AsRef<Path>
+    {
+        EmbeddedWithCId::from_file(path, Disposition::Attachment, None, ctx)
+    }
+
+    /// create a new embedding from a file
+    ///
+    /// See `Embedded::from_file`, the context is used to generate a fitting content id.
+    pub fn from_file<P>(
+        path: P,
+        disposition: Disposition,
+        fallback_media_type: Option<MediaType>,
+        ctx: &impl Context
+    ) -> Result<Self, UnknownFileTypeError>
+        where P: AsRef<Path>
+    {
+        let mut embedded = Embedded::from_file(path, disposition, fallback_media_type)?;
+        Ok(embedded.assure_content_id_and_copy(ctx))
+    }
+
+    /// create a new embedding from in-memory data
+    ///
+    /// The context is used to generate a fitting content id.
+    pub fn from_bytes<B>(
+        bytes: B,
+        media_type: MediaType,
+        file_name: Option<String>,
+        disposition: Disposition,
+        ctx: &impl Context
+    ) -> Self
+        where B: Into<Vec<u8>>
+    {
+        let mut embedded = Embedded::from_bytes(bytes, media_type, file_name, disposition);
+        embedded.assure_content_id_and_copy(ctx)
+    }
+
+    /// create a new embedding using the given content id
+    ///
+    /// Unlike the other constructors this doesn't need a context as
+    /// no content id has to be generated.
+    pub fn with_cid(resource: Resource, disposition: Disposition, content_id: ContentId) -> Self {
+        EmbeddedWithCId {
+            inner: Embedded::with_content_id(resource, disposition, content_id)
+        }
+    }
+
     /// Tries to convert an `Embedded` instance to an `EmbeddedWithCId` instance.
     ///
     /// # Error
@@ -304,6 +764,48 @@ impl EmbeddedWithCId {
     pub fn content_id(&self) -> &ContentId {
         self.inner.content_id().unwrap()
     }
+
+    /// set the file name returning the previous one, if any
+    ///
+    /// See `Embedded::set_file_name`.
+    pub fn set_file_name<I>(&mut self, file_name: I) -> Option<String>
+        where I: Into<String>
+    {
+        self.inner.set_file_name(file_name)
+    }
+
+    /// set the description returning the previous one, if any
+    ///
+    /// See `Embedded::set_description`.
+    pub fn set_description<I>(&mut self, description: I) -> Option<String>
+        where I: Into<String>
+    {
+        self.inner.set_description(description)
+    }
+
+    /// set the creation date returning the previous one, if any
+    pub fn set_creation_date(&mut self, date: DateTime) -> Option<DateTime> {
+        self.inner.set_creation_date(date)
+    }
+
+    /// set the modification date returning the previous one, if any
+    pub fn set_modification_date(&mut self, date: DateTime) -> Option<DateTime> {
+        self.inner.set_modification_date(date)
+    }
+
+    /// set the read date returning the previous one, if any
+    pub fn set_read_date(&mut self, date: DateTime) -> Option<DateTime> {
+        self.inner.set_read_date(date)
+    }
+
+    /// set the creation and modification date based on the metadata of given file
+    ///
+    /// See `Embedded::set_dates_from_file_metadata`.
+    pub fn set_dates_from_file_metadata<P>(&mut self, path: P) -> Result<(), io::Error>
+        where P: AsRef<Path>
+    {
+        self.inner.set_dates_from_file_metadata(path)
+    }
 }
 
 #[cfg(feature="serialize-to-content-id")]
@@ -317,7 +819,8 @@ impl<'a> Serialize for EmbeddedWithCId {
 impl Into<Resource> for EmbeddedWithCId {
     fn into(self) -> Resource {
         let EmbeddedWithCId { inner } = self;
-        let Embedded { content_id:_, resource, disposition:_ } = inner;
+        let Embedded { content_id:_, resource, disposition:_, file_name:_, description:_,
+            creation_date:_, modification_date:_, read_date:_, fingerprint:_ } = inner;
         resource
     }
 }
@@ -326,7 +829,8 @@ impl Into<(ContentId, Resource)> for EmbeddedWithCId {
 
     fn into(self) -> (ContentId, Resource) {
         let EmbeddedWithCId { inner } = self;
-        let Embedded { content_id, resource, disposition:_ } = inner;
+        let Embedded { content_id, resource, disposition:_, file_name:_, description:_,
+            creation_date:_, modification_date:_, read_date:_, fingerprint:_ } = inner;
         (content_id.unwrap(), resource)
     }
 }
@@ -334,27 +838,32 @@ impl Into<(ContentId, Resource)> for EmbeddedWithCId {
 
 
 
+/// Creates the `Context` used by the tests of this module and its sub-modules.
 #[cfg(test)]
-mod test {
+pub(crate) fn test_ctx() -> impl Context {
     use soft_ascii_string::SoftAsciiString;
-    use mail::{Context, Resource};
-    use mail::default_impl::simple_context;
-    use headers::components::{ContentId, Domain};
     use headers::HeaderTryFrom;
+    use headers::components::Domain;
+    use mail::default_impl::simple_context;
 
-    use ::resource::Disposition;
+    simple_context::new(
+        Domain::try_from("hy.test").unwrap(),
+        SoftAsciiString::from_unchecked("9ddqdq")
+    ).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use mail::{Context, Resource, ResourceSpec};
+    use headers::components::{ContentId, MediaType};
+
+    use ::resource::{Disposition, test_ctx};
 
     fn any_resource() -> Resource {
         Resource::sourceless_from_string("abc")
     }
 
-    fn ctx() -> impl Context {
-        simple_context::new(
-            Domain::try_from("hy.test").unwrap(),
-            SoftAsciiString::from_unchecked("9ddqdq")
-        ).unwrap()
-    }
-
     mod Embedded {
         #![allow(non_snake_case)]
 
@@ -377,7 +886,7 @@ mod test {
 
         #[test]
         fn assure_content_id_create_a_content_id() {
-            let ctx = ctx();
+            let ctx = test_ctx();
             let mut emb = Embedded::inline(any_resource());
             assert_eq!(emb.content_id(), None);
 
@@ -387,7 +896,7 @@ mod test {
 
         #[test]
         fn assure_content_id_create_a_content_id_only_if_needed() {
-            let ctx = ctx();
+            let ctx = test_ctx();
             let mut emb = Embedded::inline(any_resource());
             assert_eq!(emb.content_id(), None);
 
@@ -401,6 +910,248 @@ mod test {
             emb.assure_content_id(&ctx);
             assert_eq!(emb.content_id(), Some(&cid));
         }
+
+        #[test]
+        fn set_content_id_replaces_the_content_id() {
+            let ctx = test_ctx();
+            let mut emb = Embedded::inline(any_resource());
+            let cid = ctx.generate_content_id();
+            let cid2 = ctx.generate_content_id();
+
+            assert_eq!(emb.set_content_id(cid.clone()), None);
+            assert_eq!(emb.content_id(), Some(&cid));
+
+            assert_eq!(emb.set_content_id(cid2.clone()), Some(cid));
+            assert_eq!(emb.content_id(), Some(&cid2));
+        }
+
+        #[test]
+        fn take_content_id_removes_the_content_id() {
+            let ctx = test_ctx();
+            let mut emb = Embedded::inline(any_resource());
+            let cid = emb.assure_content_id(&ctx).clone();
+
+            assert_eq!(emb.take_content_id(), Some(cid));
+            assert_eq!(emb.content_id(), None);
+            assert_eq!(emb.take_content_id(), None);
+        }
+
+        #[test]
+        fn inline_file_uses_the_file_name() {
+            let emb = Embedded::inline_file("./templates/logo.png").unwrap();
+
+            assert_eq!(emb.disposition(), Disposition::Inline);
+            assert_eq!(emb.file_name(), Some("logo.png"));
+        }
+
+        #[test]
+        fn attachment_file_uses_disposition_attachment() {
+            let emb = Embedded::attachment_file("./Terms.PDF").unwrap();
+
+            assert_eq!(emb.disposition(), Disposition::Attachment);
+            assert_eq!(emb.file_name(), Some("Terms.PDF"));
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn from_file_sets_no_file_name_if_it_is_not_utf8() {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = Path::new(OsStr::from_bytes(b"./logo_\xff.png"));
+            let emb = Embedded::from_file(path, Disposition::Inline, None).unwrap();
+
+            assert_eq!(emb.file_name(), None);
+        }
+
+        #[test]
+        fn from_file_fails_for_unknown_extensions() {
+            let err = Embedded::attachment_file("./export.dat").unwrap_err();
+            assert_eq!(err.path(), Path::new("./export.dat"));
+        }
+
+        #[test]
+        fn from_file_uses_the_fallback_media_type_for_unknown_extensions() {
+            let fallback = MediaType::parse("application/octet-stream").unwrap();
+            let emb = Embedded::from_file("./export.dat", Disposition::Attachment, Some(fallback));
+
+            assert_eq!(emb.unwrap().file_name(), Some("export.dat"));
+        }
+
+        #[test]
+        fn from_bytes_uses_the_given_file_name() {
+            let media_type = MediaType::parse("image/png").unwrap();
+            let emb = Embedded::from_bytes(vec![0x89, 0x50], media_type,
+                Some("logo.png".to_owned()), Disposition::Inline);
+
+            assert_eq!(emb.disposition(), Disposition::Inline);
+            assert_eq!(emb.file_name(), Some("logo.png"));
+            assert_eq!(emb.content_id(), None);
+        }
+
+        #[test]
+        fn from_data_uri_creates_inline_embedding() {
+            let emb = Embedded::from_data_uri("data:image/png;base64,iVBORw0=").unwrap();
+
+            assert_eq!(emb.disposition(), Disposition::Inline);
+            assert_eq!(emb.file_name(), None);
+            assert_eq!(emb.content_id(), None);
+        }
+
+        #[test]
+        fn has_no_file_name_by_default() {
+            let emb = Embedded::attachment(any_resource());
+            assert_eq!(emb.file_name(), None);
+        }
+
+        #[test]
+        fn file_name_defaults_to_the_source_file_name() {
+            let resource = Resource::from_spec(ResourceSpec {
+                path: "./templates/Übersicht.pdf".into(),
+                use_name: None,
+                media_type: MediaType::parse("application/pdf").unwrap()
+            });
+
+            let emb = Embedded::attachment(resource);
+            assert_eq!(emb.file_name(), Some("Übersicht.pdf"));
+        }
+
+        #[test]
+        fn file_name_defaults_to_the_use_name_of_the_source() {
+            let resource = Resource::from_spec(ResourceSpec {
+                path: "./templates/a8f3.pdf".into(),
+                use_name: Some("report.pdf".into()),
+                media_type: MediaType::parse("application/pdf").unwrap()
+            });
+
+            let emb = Embedded::with_content_id(resource, Disposition::Attachment,
+                test_ctx().generate_content_id());
+            assert_eq!(emb.file_name(), Some("report.pdf"));
+        }
+
+        #[test]
+        fn set_file_name_returns_the_previous_file_name() {
+            let mut emb = Embedded::attachment(any_resource());
+
+            assert_eq!(emb.set_file_name("Übersicht.pdf"), None);
+            assert_eq!(emb.file_name(), Some("Übersicht.pdf"));
+
+            let old = emb.set_file_name("overview.pdf");
+            assert_eq!(old, Some("Übersicht.pdf".to_owned()));
+            assert_eq!(emb.take_file_name(), Some("overview.pdf".to_owned()));
+            assert_eq!(emb.file_name(), None);
+        }
+
+        #[test]
+        fn set_description_returns_the_previous_description() {
+            let mut emb = Embedded::inline(any_resource());
+            assert_eq!(emb.description(), None);
+
+            assert_eq!(emb.set_description("Firmenlogo in Grün"), None);
+            assert_eq!(emb.description(), Some("Firmenlogo in Grün"));
+
+            assert_eq!(emb.take_description(), Some("Firmenlogo in Grün".to_owned()));
+            assert_eq!(emb.description(), None);
+        }
+
+        #[test]
+        fn has_no_dates_by_default() {
+            let emb = Embedded::attachment(any_resource());
+            assert_eq!(emb.creation_date(), None);
+            assert_eq!(emb.modification_date(), None);
+            assert_eq!(emb.read_date(), None);
+        }
+
+        #[test]
+        fn set_dates_from_file_metadata_sets_the_modification_date() {
+            let mut emb = Embedded::attachment(any_resource());
+
+            emb.set_dates_from_file_metadata("./Cargo.toml").unwrap();
+            assert!(emb.modification_date().is_some());
+
+            let date = emb.take_modification_date();
+            assert!(date.is_some());
+            assert_eq!(emb.modification_date(), None);
+        }
+
+        #[test]
+        fn set_dates_from_file_metadata_does_not_set_the_read_date() {
+            let mut emb = Embedded::attachment(any_resource());
+
+            emb.set_dates_from_file_metadata("./Cargo.toml").unwrap();
+            assert_eq!(emb.read_date(), None);
+        }
+
+        #[test]
+        fn set_dates_from_file_metadata_fails_for_missing_files() {
+            let mut emb = Embedded::attachment(any_resource());
+            let res = emb.set_dates_from_file_metadata("./this/file/does/not.exist");
+            assert!(res.is_err());
+            assert_eq!(emb.modification_date(), None);
+        }
+    }
+
+    mod InspectEmbeddedResources {
+        #![allow(non_snake_case)]
+        use super::*;
+        use super::super::{Embedded, InspectEmbeddedResources};
+
+        fn any_embeddings() -> Vec<Embedded> {
+            vec![
+                Embedded::inline(any_resource()),
+                Embedded::attachment(any_resource()),
+                Embedded::inline(any_resource())
+            ]
+        }
+
+        #[test]
+        fn try_inspect_resources_visits_all_if_there_is_no_error() {
+            let data = any_embeddings();
+            let mut count = 0;
+
+            let res: Result<(), ()> = data.try_inspect_resources(&mut |_| {
+                count += 1;
+                Ok(())
+            });
+
+            assert_eq!(res, Ok(()));
+            assert_eq!(count, 3);
+        }
+
+        #[test]
+        fn try_inspect_resources_stops_at_the_first_error() {
+            let data = any_embeddings();
+            let mut count = 0;
+
+            let res = data.try_inspect_resources(&mut |emb| {
+                count += 1;
+                match emb.disposition() {
+                    Disposition::Attachment => Err(count),
+                    Disposition::Inline => Ok(())
+                }
+            });
+
+            assert_eq!(res, Err(2));
+            assert_eq!(count, 2);
+        }
+
+        #[test]
+        fn try_inspect_resources_mut_stops_at_the_first_error() {
+            let ctx = test_ctx();
+            let mut data = any_embeddings();
+
+            let res = data.try_inspect_resources_mut(&mut |emb| {
+                if emb.disposition() == Disposition::Attachment {
+                    return Err("attachment");
+                }
+                emb.assure_content_id(&ctx);
+                Ok(())
+            });
+
+            assert_eq!(res, Err("attachment"));
+            assert!(data[0].content_id().is_some());
+            assert!(data[2].content_id().is_none());
+        }
     }
 
     mod EmbeddedWithCId {
@@ -410,12 +1161,50 @@ mod test {
 
         #[test]
         fn generates_a_cid() {
-            let ctx = ctx();
+            let ctx = test_ctx();
 
             let emb_wcid = EmbeddedWithCId::inline(any_resource(), &ctx);
             let emb: &Embedded = &emb_wcid;
             assert!(emb.content_id().is_some());
             let _: &ContentId = emb_wcid.content_id();
         }
+
+        #[test]
+        fn file_and_bytes_constructors_generate_a_cid() {
+            let ctx = test_ctx();
+            let media_type = MediaType::parse("text/plain; charset=utf-8").unwrap();
+
+            let from_file = EmbeddedWithCId::attachment_file("./notes.txt", &ctx).unwrap();
+            let from_bytes = EmbeddedWithCId::from_bytes(
+                "hy", media_type, None, Disposition::Attachment, &ctx);
+
+            assert_ne!(from_file.content_id(), from_bytes.content_id());
+            assert_eq!(from_file.file_name(), Some("notes.txt"));
+        }
+
+        #[test]
+        fn with_cid_uses_the_given_cid() {
+            let cid = test_ctx().generate_content_id();
+
+            let emb_wcid = EmbeddedWithCId::with_cid(any_resource(), Disposition::Inline, cid.clone());
+            assert_eq!(emb_wcid.content_id(), &cid);
+            assert_eq!(emb_wcid.disposition(), Disposition::Inline);
+        }
+
+        #[test]
+        fn metadata_can_be_set_without_losing_the_cid() {
+            let ctx = test_ctx();
+            let mut emb_wcid = EmbeddedWithCId::attachment(any_resource(), &ctx);
+            let cid = emb_wcid.content_id().clone();
+
+            assert_eq!(emb_wcid.set_description("Quarterly report"), None);
+            emb_wcid.set_dates_from_file_metadata("./Cargo.toml").unwrap();
+            let modified = emb_wcid.modification_date().cloned().unwrap();
+            assert_eq!(emb_wcid.set_read_date(modified.clone()), None);
+
+            assert_eq!(emb_wcid.description(), Some("Quarterly report"));
+            assert_eq!(emb_wcid.read_date(), Some(&modified));
+            assert_eq!(emb_wcid.content_id(), &cid);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,541 @@
+//! Helper functions build on top of `InspectEmbeddedResources`.
+use std::fs;
+
+use mail::Context;
+use headers::components::ContentId;
+
+use super::{InspectEmbeddedResources, Embedded, EmbeddedWithCId, Disposition};
+use super::fingerprint::Fingerprint;
+
+/// Calls given function with every `Embedded` instance contained in data.
+pub fn for_each_resource<D, F>(data: &D, mut func: F)
+    where D: InspectEmbeddedResources + ?Sized, F: FnMut(&Embedded)
+{
+    data.inspect_resources(&mut func)
+}
+
+/// Calls given function with a mutable reference to every `Embedded` instance contained in data.
+pub fn for_each_resource_mut<D, F>(data: &mut D, mut func: F)
+    where D: InspectEmbeddedResources + ?Sized, F: FnMut(&mut Embedded)
+{
+    data.inspect_resources_mut(&mut func)
+}
+
+/// Like `for_each_resource` but the function can fail.
+///
+/// Once the function returned an error it is not called again and the
+/// error is returned, the remaining `Embedded` instances are still
+/// traversed. Unlike `InspectEmbeddedResources::try_inspect_resources`
+/// this also works with unsized data like slices or trait objects.
+pub fn try_for_each_resource<D, E, F>(data: &D, mut func: F) -> Result<(), E>
+    where D: InspectEmbeddedResources + ?Sized, F: FnMut(&Embedded) -> Result<(), E>
+{
+    let mut result = Ok(());
+    data.inspect_resources(&mut |embedded: &Embedded| {
+        if result.is_ok() {
+            result = func(embedded);
+        }
+    });
+    result
+}
+
+/// Like `for_each_resource_mut` but the function can fail.
+///
+/// Once the function returned an error it is not called again and the
+/// error is returned, the remaining `Embedded` instances are still
+/// traversed. Unlike `InspectEmbeddedResources::try_inspect_resources_mut`
+/// this also works with unsized data like slices or trait objects.
+pub fn try_for_each_resource_mut<D, E, F>(data: &mut D, mut func: F) -> Result<(), E>
+    where D: InspectEmbeddedResources + ?Sized, F: FnMut(&mut Embedded) -> Result<(), E>
+{
+    let mut result = Ok(());
+    data.inspect_resources_mut(&mut |embedded: &mut Embedded| {
+        if result.is_ok() {
+            result = func(embedded);
+        }
+    });
+    result
+}
+
+/// Returns the number of `Embedded` instances contained in data.
+pub fn count_resources<D>(data: &D) -> usize
+    where D: InspectEmbeddedResources + ?Sized
+{
+    let mut count = 0;
+    for_each_resource(data, |_| count += 1);
+    count
+}
+
+/// Returns the estimated size (in bytes) of the content of all `Embedded` instances contained in data.
+///
+/// File backed resources (resources with a source) are stat'd to get the
+/// size of the file, files which can not be accessed count as 0. The size
+/// of in-memory data is only known for instances created through
+/// `Embedded::from_bytes` (or `from_data_uri`), other instances without a
+/// source count as 0. The size is the size of the unencoded content, it
+/// does not include any transfer encoding overhead.
+pub fn total_estimated_size<D>(data: &D) -> u64
+    where D: InspectEmbeddedResources + ?Sized
+{
+    let mut size = 0;
+    for_each_resource(data, |embedded| size += estimated_size(embedded));
+    size
+}
+
+fn estimated_size(embedded: &Embedded) -> u64 {
+    if let Some(spec) = embedded.resource().source() {
+        return fs::metadata(&spec.path)
+            .map(|meta| meta.len())
+            .unwrap_or(0);
+    }
+
+    embedded.fingerprint.as_ref()
+        .and_then(|fingerprint| fingerprint.data_len())
+        .map(|len| len as u64)
+        .unwrap_or(0)
+}
+
+/// Returns a clone of every `Embedded` instance contained in data.
+///
+/// Given that `Resource` instances are meant to be cheap to clone this
+/// should not be very expansive.
+pub fn clone_resources<D>(data: &D) -> Vec<Embedded>
+    where D: InspectEmbeddedResources + ?Sized
+{
+    let mut resources = Vec::new();
+    for_each_resource(data, |embedded| resources.push(embedded.clone()));
+    resources
+}
+
+/// Makes sure every `Embedded` instance contained in data has a content id.
+///
+/// Content ids are only generated for `Embedded` instances which don't
+/// have one. The number of newly generated content ids is returned.
+pub fn assure_content_ids<D, C>(data: &mut D, ctx: &C) -> usize
+    where D: InspectEmbeddedResources + ?Sized, C: Context
+{
+    let mut generated = 0;
+    for_each_resource_mut(data, |embedded| {
+        if embedded.content_id().is_none() {
+            embedded.assure_content_id(ctx);
+            generated += 1;
+        }
+    });
+    generated
+}
+
+/// Makes sure every `Embedded` instance contained in data has a content id and returns copies of them.
+///
+/// The copies are returned in the order in which the `Embedded` instances
+/// are visited.
+pub fn assure_content_ids_and_copy<D, C>(data: &mut D, ctx: &C) -> Vec<EmbeddedWithCId>
+    where D: InspectEmbeddedResources + ?Sized, C: Context
+{
+    let mut copies = Vec::new();
+    for_each_resource_mut(data, |embedded| {
+        copies.push(embedded.assure_content_id_and_copy(ctx));
+    });
+    copies
+}
+
+/// Makes `Embedded` instances with the same content share a content id.
+///
+/// Only instances whose resource has a source (e.g. created through
+/// `Embedded::from_file` or from a `Resource::from_spec` resource) and
+/// instances created through `Embedded::from_bytes` or `Embedded::from_data_uri`
+/// are compared. Files are compared by their canonical path and modification
+/// time, in-memory data by its content. Instances with a different
+/// disposition, media type, file name or description are never merged, as
+/// only one of them ends up in the mail.
+///
+/// The first instance with a given content gets a content id (generated
+/// through the context if it has none) which is then set for all later
+/// instances with the same content. As copies with the same content id
+/// are only added once when composing a mail, the content is only emitted
+/// once and all `cid:` references resolve to it. This has to be called
+/// before the data is passed to the template engine.
+///
+/// Returns the number of instances which got the content id of a previous
+/// instance.
+pub fn dedup_embedded<D, C>(data: &mut D, ctx: &C) -> usize
+    where D: InspectEmbeddedResources + ?Sized, C: Context
+{
+    let mut seen: Vec<(DedupKey, ContentId)> = Vec::new();
+    let mut merged = 0;
+    for_each_resource_mut(data, |embedded| {
+        let key = match DedupKey::of(embedded) {
+            Some(key) => key,
+            None => return
+        };
+
+        let known = seen.iter()
+            .position(|&(ref other, _)| *other == key);
+
+        match known {
+            Some(idx) => {
+                embedded.set_content_id(seen[idx].1.clone());
+                merged += 1;
+            },
+            None => {
+                let content_id = embedded.assure_content_id(ctx).clone();
+                seen.push((key, content_id));
+            }
+        }
+    });
+    merged
+}
+
+/// everything which has to be the same for `dedup_embedded` to merge two `Embedded` instances
+#[derive(PartialEq)]
+struct DedupKey {
+    fingerprint: Fingerprint,
+    disposition: Disposition,
+    file_name: Option<String>,
+    description: Option<String>
+}
+
+impl DedupKey {
+
+    /// returns the key of the embedding, if it has a fingerprint
+    fn of(embedded: &Embedded) -> Option<Self> {
+        embedded.fingerprint.as_ref().map(|fingerprint| DedupKey {
+            fingerprint: fingerprint.resolve(),
+            disposition: embedded.disposition(),
+            file_name: embedded.file_name().map(|name| name.to_owned()),
+            description: embedded.description().map(|desc| desc.to_owned())
+        })
+    }
+}
+
+/// Removes all but the first `EmbeddedWithCId` instance with a given content id.
+///
+/// Clones of an `Embedded` instance made after it got a content id refer to
+/// the same content, so only one of them should be placed into the mail.
+/// Note that embeddings are compared by content id only, not by content,
+/// see `dedup_embedded` for giving embeddings with the same content the
+/// same content id.
+pub fn dedup_by_content_id(embeddings: &mut Vec<EmbeddedWithCId>) {
+    let mut idx = 0;
+    while idx < embeddings.len() {
+        let is_duplicate = embeddings[..idx].iter()
+            .any(|other| other.content_id() == embeddings[idx].content_id());
+
+        if is_duplicate {
+            embeddings.remove(idx);
+        } else {
+            idx += 1;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use mail::{Context, Resource, ResourceSpec};
+    use headers::components::MediaType;
+    use ::resource::{Embedded, Disposition, InspectEmbeddedResources, test_ctx};
+    use super::*;
+
+    #[derive(InspectEmbeddedResources)]
+    struct Outer {
+        name: String,
+        items: Vec<Item>
+    }
+
+    #[derive(InspectEmbeddedResources)]
+    struct Item {
+        count: u32,
+        image: Option<Embedded>
+    }
+
+    fn any_nested_data() -> Outer {
+        let any_item = |embedded| Item { count: 1, image: embedded };
+        Outer {
+            name: "nested".to_owned(),
+            items: vec![
+                any_item(Some(Embedded::inline(Resource::sourceless_from_string("abc")))),
+                any_item(None),
+                any_item(Some(Embedded::attachment(Resource::sourceless_from_string("def")))),
+            ]
+        }
+    }
+
+    fn any_data() -> (Vec<Embedded>, Option<Embedded>, String) {
+        (
+            vec![
+                Embedded::inline(Resource::sourceless_from_string("abc")),
+                Embedded::attachment(Resource::sourceless_from_string("def"))
+            ],
+            Some(Embedded::inline(Resource::sourceless_from_string("ghi"))),
+            "text".to_owned()
+        )
+    }
+
+    #[test]
+    fn count_resources_counts_all_embeddings() {
+        assert_eq!(count_resources(&any_data()), 3);
+        assert_eq!(count_resources(&Vec::<Embedded>::new()), 0);
+    }
+
+    #[test]
+    fn total_estimated_size_stats_files() {
+        let cargo_toml = fs::metadata("Cargo.toml").unwrap().len();
+        let data = vec![
+            Embedded::from_file("Cargo.toml", Disposition::Attachment, Some(text_plain())).unwrap(),
+            Embedded::from_file("./this/file/does/not.exist", Disposition::Attachment, Some(text_plain())).unwrap(),
+        ];
+
+        assert_eq!(total_estimated_size(&data), cargo_toml);
+    }
+
+    #[test]
+    fn total_estimated_size_uses_the_length_of_in_memory_data() {
+        let data = (
+            from_bytes("abc", Disposition::Inline),
+            Some(from_bytes("defgh", Disposition::Attachment)),
+            // in-memory data of unknown size
+            Embedded::inline(Resource::sourceless_from_string("ijk"))
+        );
+
+        assert_eq!(total_estimated_size(&data), 8);
+        assert_eq!(total_estimated_size(&Vec::<Embedded>::new()), 0);
+    }
+
+    #[test]
+    fn clone_resources_clones_in_visiting_order() {
+        let resources = clone_resources(&any_data());
+
+        let dispositions = resources.iter()
+            .map(|emb| emb.disposition())
+            .collect::<Vec<_>>();
+
+        assert_eq!(dispositions, vec![
+            Disposition::Inline, Disposition::Attachment, Disposition::Inline
+        ]);
+    }
+
+    #[test]
+    fn for_each_resource_mut_can_modify_embeddings() {
+        let mut data = any_data();
+
+        for_each_resource_mut(&mut data, |emb| { emb.set_file_name("file.txt"); });
+
+        let mut count = 0;
+        for_each_resource(&data, |emb| {
+            assert_eq!(emb.file_name(), Some("file.txt"));
+            count += 1;
+        });
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn try_for_each_resource_stops_at_the_first_error() {
+        let data = any_data();
+        let slice: &[Embedded] = &data.0;
+
+        let mut visited = 0;
+        let res = try_for_each_resource(slice, |emb| {
+            visited += 1;
+            match emb.disposition() {
+                Disposition::Attachment => Err("attachment"),
+                Disposition::Inline => Ok(())
+            }
+        });
+
+        assert_eq!(res, Err("attachment"));
+        assert_eq!(visited, 2);
+
+        assert_eq!(try_for_each_resource(&data.2, |_| Err("not visited")), Ok(()));
+    }
+
+    #[test]
+    fn try_for_each_resource_mut_works_with_trait_objects() {
+        let ctx = test_ctx();
+        let mut data = any_nested_data();
+
+        let mut visited = 0;
+        let res = {
+            let dyn_data: &mut InspectEmbeddedResources = &mut data;
+            try_for_each_resource_mut(dyn_data, |emb| {
+                visited += 1;
+                emb.assure_content_id(&ctx);
+                Err(())
+            })
+        };
+
+        assert_eq!(res, Err(()));
+        assert_eq!(visited, 1);
+        assert!(data.items[0].image.as_ref().unwrap().content_id().is_some());
+        assert!(data.items[2].image.as_ref().unwrap().content_id().is_none());
+    }
+
+    #[test]
+    fn assure_content_ids_returns_the_number_of_generated_ids() {
+        let ctx = test_ctx();
+        let mut data = any_nested_data();
+
+        {
+            let first = data.items[0].image.as_mut().unwrap();
+            first.assure_content_id(&ctx);
+        }
+
+        assert_eq!(assure_content_ids(&mut data, &ctx), 1);
+        for_each_resource(&data, |emb| assert!(emb.content_id().is_some()));
+
+        assert_eq!(assure_content_ids(&mut data, &ctx), 0);
+    }
+
+    #[test]
+    fn assure_content_ids_and_copy_returns_copies_with_the_same_ids() {
+        let ctx = test_ctx();
+        let mut data = any_nested_data();
+
+        let copies = assure_content_ids_and_copy(&mut data, &ctx);
+        assert_eq!(copies.len(), 2);
+
+        let mut idx = 0;
+        for_each_resource(&data, |emb| {
+            assert_eq!(emb.content_id(), Some(copies[idx].content_id()));
+            assert_eq!(emb.disposition(), copies[idx].disposition());
+            idx += 1;
+        });
+        assert_eq!(idx, 2);
+    }
+
+    fn text_plain() -> MediaType {
+        MediaType::parse("text/plain").unwrap()
+    }
+
+    fn from_bytes(bytes: &str, disposition: Disposition) -> Embedded {
+        Embedded::from_bytes(bytes, text_plain(), None, disposition)
+    }
+
+    #[test]
+    fn dedup_embedded_shares_the_content_id_of_same_data() {
+        let ctx = test_ctx();
+        let mut data = vec![
+            from_bytes("abc", Disposition::Inline),
+            from_bytes("def", Disposition::Inline),
+            from_bytes("abc", Disposition::Inline),
+        ];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 1);
+
+        assert!(data[0].content_id().is_some());
+        assert_eq!(data[2].content_id(), data[0].content_id());
+        assert!(data[1].content_id().is_some());
+        assert_ne!(data[1].content_id(), data[0].content_id());
+    }
+
+    #[test]
+    fn dedup_embedded_keeps_existing_content_ids_of_the_first_instance() {
+        let ctx = test_ctx();
+        let cid = ctx.generate_content_id();
+        let mut first = from_bytes("abc", Disposition::Inline);
+        first.set_content_id(cid.clone());
+        let mut data = vec![first, from_bytes("abc", Disposition::Inline)];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 1);
+
+        assert_eq!(data[0].content_id(), Some(&cid));
+        assert_eq!(data[1].content_id(), Some(&cid));
+    }
+
+    #[test]
+    fn dedup_embedded_does_not_merge_different_dispositions() {
+        let ctx = test_ctx();
+        let mut data = vec![
+            from_bytes("abc", Disposition::Inline),
+            from_bytes("abc", Disposition::Attachment),
+        ];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 0);
+        assert_ne!(data[0].content_id(), data[1].content_id());
+    }
+
+    #[test]
+    fn dedup_embedded_compares_files_by_canonical_path() {
+        let ctx = test_ctx();
+        let mut data = vec![
+            Embedded::from_file("Cargo.toml", Disposition::Attachment, Some(text_plain())).unwrap(),
+            Embedded::from_file("./src/../Cargo.toml", Disposition::Attachment, Some(text_plain())).unwrap(),
+        ];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 1);
+        assert_eq!(data[0].content_id(), data[1].content_id());
+    }
+
+    #[test]
+    fn dedup_embedded_compares_resources_with_a_source() {
+        let ctx = test_ctx();
+        let resource = || Resource::from_spec(ResourceSpec {
+            path: "Cargo.toml".into(),
+            use_name: None,
+            media_type: text_plain()
+        });
+        let mut data = vec![Embedded::inline(resource()), Embedded::inline(resource())];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 1);
+        assert_eq!(data[0].content_id(), data[1].content_id());
+    }
+
+    #[test]
+    fn dedup_embedded_does_not_merge_different_file_names() {
+        let ctx = test_ctx();
+        let named = |file_name: &str| Embedded::from_bytes(
+            "same report", text_plain(), Some(file_name.to_owned()), Disposition::Attachment);
+        let mut data = vec![named("jan.pdf"), named("feb.pdf"), named("jan.pdf")];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 1);
+        assert_ne!(data[0].content_id(), data[1].content_id());
+        assert_eq!(data[0].content_id(), data[2].content_id());
+    }
+
+    #[test]
+    fn dedup_embedded_does_not_merge_different_descriptions() {
+        let ctx = test_ctx();
+        let mut first = from_bytes("abc", Disposition::Attachment);
+        first.set_description("first");
+        let mut second = from_bytes("abc", Disposition::Attachment);
+        second.set_description("second");
+        let mut data = vec![first, second];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 0);
+        assert_ne!(data[0].content_id(), data[1].content_id());
+    }
+
+    #[test]
+    fn dedup_embedded_ignores_embeddings_without_fingerprint() {
+        let ctx = test_ctx();
+        let mut replaced = from_bytes("abc", Disposition::Inline);
+        let _ = replaced.resource_mut();
+        let mut data = vec![
+            Embedded::inline(Resource::sourceless_from_string("abc")),
+            from_bytes("abc", Disposition::Inline),
+            replaced,
+        ];
+
+        assert_eq!(dedup_embedded(&mut data, &ctx), 0);
+        assert!(data[0].content_id().is_none());
+        assert!(data[2].content_id().is_none());
+    }
+
+    #[test]
+    fn dedup_by_content_id_keeps_the_first_of_each_content_id() {
+        let ctx = test_ctx();
+        let mut logo = Embedded::inline(Resource::sourceless_from_string("abc"));
+        logo.assure_content_id(&ctx);
+        let other = Embedded::inline(Resource::sourceless_from_string("abc"));
+
+        let mut data = vec![logo.clone(), other, logo];
+        let mut copies = assure_content_ids_and_copy(&mut data, &ctx);
+        assert_eq!(copies.len(), 3);
+
+        dedup_by_content_id(&mut copies);
+
+        assert_eq!(copies.len(), 2);
+        assert_eq!(Some(copies[0].content_id()), data[0].content_id());
+        assert_eq!(Some(copies[1].content_id()), data[1].content_id());
+    }
+}
@@ -87,14 +87,8 @@ pub trait TemplateEngine<C, D>
 /// `TemplateEngine` trait and consumed by this crate to generate
 /// a `Mail` instance.
 ///
-//TODO[LATER]
-/// # Current Limitations
-///
-/// Currently there is no way to pass in `MailParts` from an external
-/// point to produce a `Mail` instance. I.e. the only way they are
-/// used is if `MailSendData` is used to create a mail and the procedure
-/// calls the `TemplateEngine` to get it's mail parts. This might change
-/// in future versions.
+/// `MailParts` created outside of a `TemplateEngine` can be turned
+/// into a `Mail` using `MailSendData::compose_from_parts`.
 pub struct MailParts {
     /// A vector of alternate bodies
     ///
@@ -118,6 +112,43 @@ pub struct MailParts {
     pub attachments: Vec<EmbeddedWithCId>
 }
 
+impl MailParts {
+
+    /// Create `MailParts` from the `(bodies, attachments)` tuple used by older template engines.
+    ///
+    /// The resulting `MailParts` have no shared embeddings (embeddings
+    /// specific to a body are still part of it's `BodyPart`).
+    ///
+    /// This only exists to ease porting older template engines, it will be
+    /// removed together with the `From` impl for the tuple with the next
+    /// breaking release. Create `MailParts` directly instead.
+    #[deprecated(since = "0.2.0", note = "create `MailParts` directly instead")]
+    pub fn from_legacy(
+        alternative_bodies: Vec1<BodyPart>,
+        attachments: Vec<EmbeddedWithCId>
+    ) -> Self {
+        MailParts {
+            alternative_bodies,
+            shared_embeddings: Vec::new(),
+            attachments
+        }
+    }
+}
+
+/// Converts the `(bodies, attachments)` tuple used by older template engines.
+///
+/// Deprecated like `MailParts::from_legacy` and removed with the next breaking
+/// release (trait impls can not be marked `#[deprecated]`).
+impl From<(Vec1<BodyPart>, Vec<EmbeddedWithCId>)> for MailParts {
+    fn from((alternative_bodies, attachments): (Vec1<BodyPart>, Vec<EmbeddedWithCId>)) -> Self {
+        MailParts {
+            alternative_bodies,
+            shared_embeddings: Vec::new(),
+            attachments
+        }
+    }
+}
+
 macro_rules! impl_for_1elem_container {
     ($($name:ident),*) => ($(
         impl<C, D, T> TemplateEngine<C, D> for $name<T>
@@ -196,4 +227,26 @@ mod test {
         }
 
     }
+
+    mod MailParts {
+        #![allow(non_snake_case)]
+
+        use mail::Resource;
+        use ::builder_extension::BodyPart;
+        use super::super::MailParts;
+
+        #[test]
+        fn from_legacy_tuple_has_no_shared_embeddings() {
+            let body = BodyPart {
+                resource: Resource::sourceless_from_string("hy"),
+                embeddings: Vec::new()
+            };
+
+            let parts: MailParts = (vec1![body], Vec::new()).into();
+
+            assert_eq!(parts.alternative_bodies.len(), 1);
+            assert!(parts.shared_embeddings.is_empty());
+            assert!(parts.attachments.is_empty());
+        }
+    }
 }
\ No newline at end of file